@@ -1,4 +1,6 @@
 use crate::api::get_stored_credentials;
+use crate::api_config_cache;
+use crate::http::{self, EndpointKind};
 use serde::{Deserialize, Serialize};
 use std::env;
 use std::fs;
@@ -90,6 +92,8 @@ pub async fn secure_storage_save(app: AppHandle, items: Vec<StorageItem>) -> Res
     fs::write(&storage_path, content)
         .map_err(|e| format!("Failed to write storage file: {}", e))?;
 
+    // New license or model: cached provider configs no longer apply
+    api_config_cache::clear(&app);
     Ok(())
 }
 
@@ -147,6 +151,7 @@ pub async fn secure_storage_remove(app: AppHandle, keys: Vec<String>) -> Result<
     fs::write(&storage_path, content)
         .map_err(|e| format!("Failed to write storage file: {}", e))?;
 
+    api_config_cache::clear(&app);
     Ok(())
 }
 
@@ -208,30 +213,30 @@ pub async fn activate_license_api(
     };
 
     // Make HTTP request to activation endpoint with authorization header
-    let client = reqwest::Client::new();
     let url = format!("{}/activate", payment_endpoint);
 
-    let response = client
-        .post(&url)
-        .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", api_access_key))
-        .json(&activation_request)
-        .send()
-        .await
-        .map_err(|e| {
-            let error_msg = format!("{}", e);
-            if error_msg.contains("url (") {
-                // Remove the URL part from the error message
-                let parts: Vec<&str> = error_msg.split(" for url (").collect();
-                if parts.len() > 1 {
-                    format!("Failed to make chat request: {}", parts[0])
-                } else {
-                    format!("Failed to make chat request: {}", error_msg)
-                }
+    let response = http::send(&app, EndpointKind::License, &http::provider_key(&url), |client| {
+        Ok(client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .header("Authorization", format!("Bearer {}", api_access_key))
+            .json(&activation_request))
+    })
+    .await
+    .map_err(|e| {
+        let error_msg = format!("{}", e);
+        if error_msg.contains("url (") {
+            // Remove the URL part from the error message
+            let parts: Vec<&str> = error_msg.split(" for url (").collect();
+            if parts.len() > 1 {
+                format!("Failed to make chat request: {}", parts[0])
             } else {
                 format!("Failed to make chat request: {}", error_msg)
             }
-        })?;
+        } else {
+            format!("Failed to make chat request: {}", error_msg)
+        }
+    })?;
 
     let activation_response: ActivationResponse = response.json().await.map_err(|e| {
         let error_msg = format!("{}", e);
@@ -265,30 +270,30 @@ pub async fn deactivate_license_api(app: AppHandle) -> Result<ActivationResponse
         app_version: app_version.clone(),
     };
     // Make HTTP request to activation endpoint with authorization header
-    let client = reqwest::Client::new();
     let url = format!("{}/deactivate", payment_endpoint);
 
-    let response = client
-        .post(&url)
-        .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", api_access_key))
-        .json(&deactivation_request)
-        .send()
-        .await
-        .map_err(|e| {
-            let error_msg = format!("{}", e);
-            if error_msg.contains("url (") {
-                // Remove the URL part from the error message
-                let parts: Vec<&str> = error_msg.split(" for url (").collect();
-                if parts.len() > 1 {
-                    format!("Failed to make chat request: {}", parts[0])
-                } else {
-                    format!("Failed to make chat request: {}", error_msg)
-                }
+    let response = http::send(&app, EndpointKind::License, &http::provider_key(&url), |client| {
+        Ok(client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .header("Authorization", format!("Bearer {}", api_access_key))
+            .json(&deactivation_request))
+    })
+    .await
+    .map_err(|e| {
+        let error_msg = format!("{}", e);
+        if error_msg.contains("url (") {
+            // Remove the URL part from the error message
+            let parts: Vec<&str> = error_msg.split(" for url (").collect();
+            if parts.len() > 1 {
+                format!("Failed to make chat request: {}", parts[0])
             } else {
                 format!("Failed to make chat request: {}", error_msg)
             }
-        })?;
+        } else {
+            format!("Failed to make chat request: {}", error_msg)
+        }
+    })?;
     let deactivation_response: ActivationResponse = response.json().await.map_err(|e| {
         let error_msg = format!("{}", e);
         if error_msg.contains("url (") {
@@ -338,30 +343,30 @@ pub async fn validate_license_api(app: AppHandle) -> Result<ValidateResponse, St
     }
 
     // Make HTTP request to validate endpoint with authorization header
-    let client = reqwest::Client::new();
     let url = format!("{}/validate", payment_endpoint);
 
-    let response = client
-        .post(&url)
-        .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", api_access_key))
-        .json(&validate_request)
-        .send()
-        .await
-        .map_err(|e| {
-            let error_msg = format!("{}", e);
-            if error_msg.contains("url (") {
-                // Remove the URL part from the error message
-                let parts: Vec<&str> = error_msg.split(" for url (").collect();
-                if parts.len() > 1 {
-                    format!("Failed to make chat request: {}", parts[0])
-                } else {
-                    format!("Failed to make chat request: {}", error_msg)
-                }
+    let response = http::send(&app, EndpointKind::License, &http::provider_key(&url), |client| {
+        Ok(client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .header("Authorization", format!("Bearer {}", api_access_key))
+            .json(&validate_request))
+    })
+    .await
+    .map_err(|e| {
+        let error_msg = format!("{}", e);
+        if error_msg.contains("url (") {
+            // Remove the URL part from the error message
+            let parts: Vec<&str> = error_msg.split(" for url (").collect();
+            if parts.len() > 1 {
+                format!("Failed to make chat request: {}", parts[0])
             } else {
                 format!("Failed to make chat request: {}", error_msg)
             }
-        })?;
+        } else {
+            format!("Failed to make chat request: {}", error_msg)
+        }
+    })?;
 
     let validate_response: ValidateResponse = response.json().await.map_err(|e| {
         let error_msg = format!("{}", e);
@@ -394,36 +399,36 @@ pub fn mask_license_key_cmd(license_key: String) -> String {
 }
 
 #[tauri::command]
-pub async fn get_checkout_url() -> Result<CheckoutResponse, String> {
+pub async fn get_checkout_url(app: AppHandle) -> Result<CheckoutResponse, String> {
     // Get payment endpoint and API access key from environment
     let payment_endpoint = get_payment_endpoint()?;
     let api_access_key = get_api_access_key()?;
 
     // Make HTTP request to checkout endpoint with authorization header
-    let client = reqwest::Client::new();
     let url = format!("{}/checkout", payment_endpoint);
 
-    let response = client
-        .post(&url)
-        .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", api_access_key))
-        .json(&serde_json::json!({}))
-        .send()
-        .await
-        .map_err(|e| {
-            let error_msg = format!("{}", e);
-            if error_msg.contains("url (") {
-                // Remove the URL part from the error message
-                let parts: Vec<&str> = error_msg.split(" for url (").collect();
-                if parts.len() > 1 {
-                    format!("Failed to make chat request: {}", parts[0])
-                } else {
-                    format!("Failed to make chat request: {}", error_msg)
-                }
+    let response = http::send(&app, EndpointKind::License, &http::provider_key(&url), |client| {
+        Ok(client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .header("Authorization", format!("Bearer {}", api_access_key))
+            .json(&serde_json::json!({})))
+    })
+    .await
+    .map_err(|e| {
+        let error_msg = format!("{}", e);
+        if error_msg.contains("url (") {
+            // Remove the URL part from the error message
+            let parts: Vec<&str> = error_msg.split(" for url (").collect();
+            if parts.len() > 1 {
+                format!("Failed to make chat request: {}", parts[0])
             } else {
                 format!("Failed to make chat request: {}", error_msg)
             }
-        })?;
+        } else {
+            format!("Failed to make chat request: {}", error_msg)
+        }
+    })?;
 
     let checkout_response: CheckoutResponse = response.json().await.map_err(|e| {
         let error_msg = format!("{}", e);
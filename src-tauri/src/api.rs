@@ -1,13 +1,41 @@
 use base64::{engine::general_purpose, Engine as _};
+use futures_util::future::join_all;
 use futures_util::StreamExt;
 use reqwest::multipart::{Form, Part};
 use reqwest::Url;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::env;
 use std::fs;
+use std::io::Cursor;
 use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Instant;
 use tauri::{AppHandle, Emitter, Manager};
 use tauri_plugin_machine_uid::MachineUidExt;
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+use crate::api_config_cache;
+use crate::chat_adapters::{
+    self, ChatInput, ChatProviderType, ReasoningEffort, ResponseFormat, ToolCallAccumulator,
+    ToolCalls,
+};
+use crate::chat_fallback::{self, ChatFallbackEntry};
+use crate::chat_tools::{self, ChatToolCallEvent};
+use crate::config_store::{JsonConfig, JsonConfigStore};
+use crate::context_window;
+use crate::direct_provider::{self, DirectProviderConfig};
+use crate::embedded_llm::{self, EmbeddedLlmConfig, EmbeddedOutcome};
+use crate::http::{self, EndpointKind, HttpError};
+use crate::image_pipeline;
+use crate::prompt_templates;
+use crate::sse::SseDecoder;
+use crate::structured_output;
+use crate::transcript_log;
+use crate::usage_budget::{self, BudgetRequest};
+use crate::usage_ledger::{self, UsageKind, UsageRecord};
+use crate::vocabulary::{self, CompiledProfile};
 
 fn get_app_endpoint() -> Result<String, String> {
     if let Ok(endpoint) = env::var("APP_ENDPOINT") {
@@ -129,7 +157,7 @@ pub struct SystemPromptResponse {
 }
 
 // API Response Configuration Structs
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ApiResponseConfig {
     url: String,
     user_token: String,
@@ -143,6 +171,50 @@ pub struct ApiResponseConfig {
     #[serde(rename = "user_audio")]
     user_audio: Option<UserAudioConfig>,
     errors: Option<Vec<ApiConfigError>>,
+    // Which wire format the chat url speaks; OpenAI-compatible when absent
+    #[serde(default)]
+    provider_type: ChatProviderType,
+}
+
+// Where a chat request goes: the license server's model config, or the
+// locally configured endpoint when direct mode is on
+struct ChatTarget {
+    url: String,
+    token: String,
+    model: String,
+    body: String,
+    provider_type: ChatProviderType,
+    errors: Option<Vec<ApiConfigError>>,
+    // Direct mode never reports usage or errors to the license server
+    direct: bool,
+}
+
+impl From<ApiResponseConfig> for ChatTarget {
+    fn from(config: ApiResponseConfig) -> Self {
+        Self {
+            url: config.url,
+            token: config.user_token,
+            model: config.model,
+            body: config.body,
+            provider_type: config.provider_type,
+            errors: config.errors,
+            direct: false,
+        }
+    }
+}
+
+impl From<DirectProviderConfig> for ChatTarget {
+    fn from(config: DirectProviderConfig) -> Self {
+        Self {
+            url: config.chat_url(),
+            token: config.api_key,
+            model: config.model,
+            body: String::new(),
+            provider_type: config.provider_type,
+            errors: None,
+            direct: true,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -172,41 +244,321 @@ pub struct UserAudioConfig {
     headers: Option<Vec<UserAudioHeader>>,
 }
 
+// Rich transcription result (verbose_json-style responses)
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TranscriptionResult {
+    pub text: String,
+    pub language: Option<String>,
+    pub duration: Option<f64>,
+    pub segments: Vec<TranscriptionSegment>,
+    pub words: Option<Vec<TranscriptionWord>>,
+    #[serde(default)]
+    pub flagged: bool,
+    #[serde(default)]
+    pub filter_reasons: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TranscriptionSegment {
+    pub id: usize,
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+    pub confidence: Option<f64>,
+    pub avg_logprob: Option<f64>,
+    pub no_speech_prob: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TranscriptionWord {
+    pub word: String,
+    pub start: f64,
+    pub end: f64,
+    pub confidence: Option<f64>,
+}
+
+// Post-transcription filter for Whisper-style hallucinations
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptFilterMode {
+    Drop,
+    Flag,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptFilterConfig {
+    pub enabled: bool,
+    pub mode: TranscriptFilterMode,
+    pub hallucination_phrases: Vec<String>,
+    pub max_ngram_repeats: usize,
+    pub min_audio_rms: f32,
+    pub min_confidence: f64,
+    pub max_no_speech_prob: f64,
+}
+
+impl Default for TranscriptFilterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            mode: TranscriptFilterMode::Drop,
+            hallucination_phrases: [
+                "thank you for watching",
+                "thanks for watching",
+                "thank you so much for watching",
+                "please subscribe",
+                "like and subscribe",
+                "subscribe to my channel",
+                "see you in the next video",
+                "subtitles by the amara.org community",
+                "you",
+            ]
+            .iter()
+            .map(|phrase| phrase.to_string())
+            .collect(),
+            max_ngram_repeats: 3,     // 4+ back-to-back repeats is a decoding loop
+            min_audio_rms: 0.005,     // Near-silent segments
+            min_confidence: 0.3,      // ~avg_logprob of -1.2
+            max_no_speech_prob: 0.6,  // Whisper's own no_speech_threshold
+        }
+    }
+}
+
+impl JsonConfig for TranscriptFilterConfig {
+    const FILE_NAME: &'static str = "transcript_filter.json";
+    const LABEL: &'static str = "transcript filter";
+}
+
+#[derive(Default)]
+pub struct TranscriptFilterState {
+    config: JsonConfigStore<TranscriptFilterConfig>,
+}
+
+// Longest phrase length checked for repetition loops
+const MAX_REPEATED_NGRAM: usize = 8;
+
+// Per-request knobs for perform_user_audio_transcription
+#[derive(Debug, Default)]
+struct TranscriptionOptions {
+    verbose: bool,
+    prompt: Option<String>,
+}
+
+// In-flight chat streams keyed by request id; sending on the handle aborts the stream
+#[derive(Default)]
+pub struct ChatStreamState {
+    streams: Mutex<HashMap<String, oneshot::Sender<()>>>,
+}
+
+// Optional per-request settings for chat_stream_response
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ChatStreamOptions {
+    // Tool definitions in the OpenAI function format
+    #[serde(default)]
+    pub tools: Vec<serde_json::Value>,
+    // Offer the local tools in chat_tools. Opt-in: a screenshot shouldn't be taken unasked.
+    #[serde(default)]
+    pub builtin_tools: bool,
+    #[serde(default)]
+    pub reasoning_effort: Option<ReasoningEffort>,
+    // JSON schema the final answer must match
+    #[serde(default)]
+    pub response_format: Option<ResponseFormat>,
+    // Extra top-level body fields (temperature, top_p, ...), applied last
+    #[serde(default)]
+    pub request_options: serde_json::Map<String, serde_json::Value>,
+}
+
+// Body fields the adapters fill in from the conversation itself (OpenAI,
+// Anthropic and Gemini spellings); request_options can't override them
+const RESERVED_REQUEST_OPTIONS: [&str; 7] = [
+    "model",
+    "messages",
+    "stream",
+    "tools",
+    "system",
+    "contents",
+    "system_instruction",
+];
+
+// Stream events carry the request id so concurrent streams can be told apart
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatStreamChunk {
+    pub request_id: String,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatStreamReasoning {
+    pub request_id: String,
+    pub content: String,
+}
+
+// Which model is answering; sent once a model produces its first token
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatStreamModel {
+    pub request_id: String,
+    pub model: String,
+    // True when an earlier model in the fallback chain failed
+    pub fallback: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatStreamComplete {
+    pub request_id: String,
+    pub text: String,
+    // Thinking text from reasoning models, separate from the answer
+    pub reasoning: Option<String>,
+    // The model that produced the final answer
+    pub model: Option<String>,
+    // The parsed answer when a response_format was requested
+    pub json: Option<serde_json::Value>,
+}
+
+// The answer didn't match the response_format schema and is being asked for
+// again; text streamed since the last round should be discarded
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatStreamFormatRetry {
+    pub request_id: String,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatStreamCancelled {
+    pub request_id: String,
+    // Whatever had streamed in before the cancel
+    pub text: String,
+    pub reasoning: Option<String>,
+}
+
+// Unregisters the stream on every return path of chat_stream_response
+struct ChatStreamGuard {
+    app: AppHandle,
+    request_id: String,
+}
+
+impl Drop for ChatStreamGuard {
+    fn drop(&mut self) {
+        let state = self.app.state::<ChatStreamState>();
+        let mut streams = match state.streams.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        streams.remove(&self.request_id);
+    }
+}
+
+fn register_chat_stream(
+    app: &AppHandle,
+    request_id: &str,
+) -> Result<(ChatStreamGuard, oneshot::Receiver<()>), String> {
+    let state = app.state::<ChatStreamState>();
+    let mut streams = state
+        .streams
+        .lock()
+        .map_err(|e| format!("Failed to register chat stream: {}", e))?;
+
+    if streams.contains_key(request_id) {
+        return Err(format!("Chat stream {} is already running", request_id));
+    }
+
+    let (abort_tx, abort_rx) = oneshot::channel();
+    streams.insert(request_id.to_string(), abort_tx);
+
+    Ok((
+        ChatStreamGuard {
+            app: app.clone(),
+            request_id: request_id.to_string(),
+        },
+        abort_rx,
+    ))
+}
+
+fn emit_chat_stream_cancelled(
+    app: &AppHandle,
+    request_id: &str,
+    text: String,
+    reasoning: String,
+) -> String {
+    let _ = app.emit(
+        "chat_stream_cancelled",
+        ChatStreamCancelled {
+            request_id: request_id.to_string(),
+            text: text.clone(),
+            reasoning: Some(reasoning).filter(|reasoning| !reasoning.is_empty()),
+        },
+    );
+    text
+}
+
 // Audio API Command
 #[tauri::command]
 pub async fn transcribe_audio(
     app: AppHandle,
     audio_base64: String,
 ) -> Result<AudioResponse, String> {
-    let (_, _, selected_model) = get_stored_credentials(&app).await?;
+    let audio_bytes = decode_audio_base64(&audio_base64)?;
+    let result = transcribe_with_user_audio_config(&app, &audio_bytes, false).await?;
+    Ok(AudioResponse {
+        success: true,
+        transcription: Some(result.text),
+        error: None,
+    })
+}
+
+// Same as transcribe_audio, but keeps language, segment and word timings
+#[tauri::command]
+pub async fn transcribe_audio_detailed(
+    app: AppHandle,
+    audio_base64: String,
+) -> Result<TranscriptionResult, String> {
+    let audio_bytes = decode_audio_base64(&audio_base64)?;
+    transcribe_with_user_audio_config(&app, &audio_bytes, true).await
+}
+
+pub(crate) async fn transcribe_with_user_audio_config(
+    app: &AppHandle,
+    audio_bytes: &[u8],
+    verbose: bool,
+) -> Result<TranscriptionResult, String> {
+    let audio_seconds = wav_duration_seconds(audio_bytes).unwrap_or(0.0);
+    usage_budget::check(app, BudgetRequest::Transcription { audio_seconds }).await?;
+
+    let (_, _, selected_model) = get_stored_credentials(app).await?;
     let provider = selected_model.as_ref().map(|model| model.provider.clone());
     let model = selected_model.as_ref().map(|model| model.model.clone());
 
-    let api_config = fetch_api_response_config(&app, provider.clone(), model.clone()).await?;
+    let api_config = fetch_api_response_config(app, provider.clone(), model.clone()).await?;
     let user_audio_config = api_config.user_audio.as_ref().ok_or_else(|| {
         "Audio transcription is not configured for this workspace. Please contact support."
             .to_string()
     })?;
 
-    let audio_bytes = decode_audio_base64(&audio_base64)?;
-    let client = reqwest::Client::new();
+    let vocabulary = vocabulary::active_profile(app);
+    let options = TranscriptionOptions {
+        verbose,
+        prompt: vocabulary
+            .as_ref()
+            .and_then(|vocabulary| vocabulary::build_prompt(&vocabulary.profile)),
+    };
     let error_provider = provider.clone();
     let error_model = model.clone();
+    let started = Instant::now();
     match perform_user_audio_transcription(
-        &client,
+        app,
         &user_audio_config.url,
         &user_audio_config.user_token,
         &user_audio_config.model,
         user_audio_config.headers.as_ref(),
-        &audio_bytes,
+        audio_bytes,
+        &options,
     )
     .await
     {
-        Ok(transcription) => Ok(AudioResponse {
-            success: true,
-            transcription: Some(transcription),
-            error: None,
-        }),
+        Ok(mut result) => {
+            finalize_transcription(app, &mut result, audio_bytes, vocabulary.as_deref());
+            record_transcription_usage(app, provider, &user_audio_config.model, &result, audio_bytes, started);
+            Ok(result)
+        }
         Err(primary_error) => {
             let fallback_error_message = if let (Some(fallback_url), Some(fallback_token)) = (
                 user_audio_config.fallback_url.as_ref(),
@@ -218,21 +570,20 @@ pub async fn transcribe_audio(
                     .unwrap_or(&user_audio_config.model);
 
                 match perform_user_audio_transcription(
-                    &client,
+                    app,
                     fallback_url,
                     fallback_token,
                     fallback_model,
                     user_audio_config.headers.as_ref(),
-                    &audio_bytes,
+                    audio_bytes,
+                    &options,
                 )
                 .await
                 {
-                    Ok(transcription) => {
-                        return Ok(AudioResponse {
-                            success: true,
-                            transcription: Some(transcription),
-                            error: None,
-                        });
+                    Ok(mut result) => {
+                        finalize_transcription(app, &mut result, audio_bytes, vocabulary.as_deref());
+                        record_transcription_usage(app, provider, fallback_model, &result, audio_bytes, started);
+                        return Ok(result);
                     }
                     Err(fallback_error) => Some(fallback_error),
                 }
@@ -240,6 +591,13 @@ pub async fn transcribe_audio(
                 Some("fallback not configured".to_string())
             };
 
+            // A rejected token may just be a stale cached config
+            if is_auth_error(&primary_error)
+                || fallback_error_message.as_deref().is_some_and(is_auth_error)
+            {
+                api_config_cache::invalidate(app, provider.as_deref(), model.as_deref());
+            }
+
             tracing::warn!(
                 primary_error = %primary_error,
                 fallback_error = %fallback_error_message
@@ -263,11 +621,325 @@ pub async fn transcribe_audio(
     }
 }
 
-// Helper function to fetch API response configuration
+#[tauri::command]
+pub async fn get_transcript_filter_config(app: AppHandle) -> Result<TranscriptFilterConfig, String> {
+    app.state::<TranscriptFilterState>().config.load(&app)
+}
+
+#[tauri::command]
+pub async fn update_transcript_filter_config(
+    app: AppHandle,
+    config: TranscriptFilterConfig,
+) -> Result<(), String> {
+    if !(0.0..=1.0).contains(&config.min_audio_rms) {
+        return Err("Invalid min_audio_rms: must be 0.0-1.0".to_string());
+    }
+    if !(0.0..=1.0).contains(&config.min_confidence) {
+        return Err("Invalid min_confidence: must be 0.0-1.0".to_string());
+    }
+    if !(0.0..=1.0).contains(&config.max_no_speech_prob) {
+        return Err("Invalid max_no_speech_prob: must be 0.0-1.0".to_string());
+    }
+
+    app.state::<TranscriptFilterState>().config.save(&app, config)
+}
+
+// Hallucination filter first, then the vocabulary rules on whatever survived
+pub(crate) fn finalize_transcription(
+    app: &AppHandle,
+    result: &mut TranscriptionResult,
+    audio_bytes: &[u8],
+    vocabulary: Option<&CompiledProfile>,
+) {
+    let config = app.state::<TranscriptFilterState>().config.load_or_default(app);
+    apply_transcript_filter(result, audio_bytes, &config);
+
+    if let Some(profile) = vocabulary {
+        vocabulary::apply_to_result(profile, result);
+    }
+
+    transcript_log::record(app, None, &result.text);
+}
+
+// Billed audio length: what the provider reports, else the WAV header's
+pub(crate) fn record_transcription_usage(
+    app: &AppHandle,
+    provider: Option<String>,
+    model: &str,
+    result: &TranscriptionResult,
+    audio_bytes: &[u8],
+    started: Instant,
+) {
+    let audio_seconds = result
+        .duration
+        .or_else(|| wav_duration_seconds(audio_bytes))
+        .unwrap_or(0.0);
+    usage_ledger::record(
+        app,
+        UsageRecord {
+            kind: UsageKind::Transcription,
+            request_id: None,
+            provider,
+            model: model.to_string(),
+            prompt_tokens: 0,
+            completion_tokens: 0,
+            audio_seconds,
+            latency_ms: started.elapsed().as_millis() as u64,
+            estimated: false,
+        },
+    );
+}
+
+fn apply_transcript_filter(
+    result: &mut TranscriptionResult,
+    audio_bytes: &[u8],
+    config: &TranscriptFilterConfig,
+) {
+    if !config.enabled {
+        return;
+    }
+
+    let drop_matches = config.mode == TranscriptFilterMode::Drop;
+    let phrases: Vec<String> = config
+        .hallucination_phrases
+        .iter()
+        .map(|phrase| normalize_transcript_text(phrase))
+        .filter(|phrase| !phrase.is_empty())
+        .collect();
+    let mut reasons = Vec::new();
+
+    // Whisper invents text for silence, so check the audio itself first
+    if let Some(rms) = wav_rms(audio_bytes) {
+        if rms < config.min_audio_rms {
+            reasons.push(format!("audio energy too low (rms {:.4})", rms));
+            if drop_matches {
+                clear_transcription(result);
+            }
+            result.flagged = true;
+            result.filter_reasons = reasons;
+            return;
+        }
+    }
+
+    let mut kept_segments = Vec::with_capacity(result.segments.len());
+    let mut dropped_segment = false;
+    for segment in result.segments.drain(..) {
+        if let Some(reason) = segment_filter_reason(&segment, &phrases, config) {
+            reasons.push(format!("segment {}: {}", segment.id, reason));
+            if drop_matches {
+                dropped_segment = true;
+                continue;
+            }
+        }
+        kept_segments.push(segment);
+    }
+    result.segments = kept_segments;
+
+    if dropped_segment {
+        result.text = result
+            .segments
+            .iter()
+            .map(|segment| segment.text.trim())
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        if let Some(words) = result.words.as_mut() {
+            words.retain(|word| {
+                result
+                    .segments
+                    .iter()
+                    .any(|segment| word.start >= segment.start && word.start < segment.end)
+            });
+        }
+    }
+
+    let normalized = normalize_transcript_text(&result.text);
+    if !normalized.is_empty() && phrases.contains(&normalized) {
+        reasons.push(format!("known hallucination phrase: \"{}\"", result.text.trim()));
+        if drop_matches {
+            clear_transcription(result);
+        }
+    } else if let Some(collapsed) = collapse_repeated_ngrams(&result.text, config.max_ngram_repeats) {
+        reasons.push("repeated phrase loop".to_string());
+        if drop_matches {
+            result.text = collapsed;
+        }
+    }
+
+    result.flagged = !reasons.is_empty();
+    result.filter_reasons = reasons;
+}
+
+fn segment_filter_reason(
+    segment: &TranscriptionSegment,
+    phrases: &[String],
+    config: &TranscriptFilterConfig,
+) -> Option<String> {
+    if phrases.contains(&normalize_transcript_text(&segment.text)) {
+        return Some("known hallucination phrase".to_string());
+    }
+
+    // Same rule Whisper uses: high no-speech probability only counts when
+    // the decoder wasn't confident in the text either
+    if let Some(no_speech_prob) = segment.no_speech_prob {
+        let low_logprob = segment.avg_logprob.is_none_or(|logprob| logprob < -1.0);
+        if no_speech_prob > config.max_no_speech_prob && low_logprob {
+            return Some(format!("no-speech probability {:.2}", no_speech_prob));
+        }
+    }
+
+    if let Some(confidence) = segment.confidence {
+        if confidence < config.min_confidence {
+            return Some(format!("low confidence {:.2}", confidence));
+        }
+    }
+
+    None
+}
+
+fn clear_transcription(result: &mut TranscriptionResult) {
+    result.text.clear();
+    result.segments.clear();
+    result.words = None;
+}
+
+fn normalize_transcript_text(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '\'' {
+                c.to_ascii_lowercase()
+            } else {
+                ' '
+            }
+        })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Collapses runs of the same n-gram repeated more than `max_repeats` times
+// back to back. Returns None when nothing was collapsed.
+fn collapse_repeated_ngrams(text: &str, max_repeats: usize) -> Option<String> {
+    if max_repeats == 0 {
+        return None;
+    }
+
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let normalized: Vec<String> = words
+        .iter()
+        .map(|word| normalize_transcript_text(word))
+        .collect();
+
+    let mut output: Vec<&str> = Vec::with_capacity(words.len());
+    let mut collapsed = false;
+    let mut i = 0;
+
+    while i < words.len() {
+        let mut skipped = 0;
+        for n in (1..=MAX_REPEATED_NGRAM).rev() {
+            if i + n * (max_repeats + 1) > words.len() {
+                continue;
+            }
+
+            let ngram = &normalized[i..i + n];
+            let mut repeats = 1;
+            while i + (repeats + 1) * n <= words.len()
+                && normalized[i + repeats * n..i + (repeats + 1) * n] == *ngram
+            {
+                repeats += 1;
+            }
+
+            if repeats > max_repeats {
+                output.extend_from_slice(&words[i..i + n]);
+                skipped = repeats * n;
+                break;
+            }
+        }
+
+        if skipped > 0 {
+            collapsed = true;
+            i += skipped;
+        } else {
+            output.push(words[i]);
+            i += 1;
+        }
+    }
+
+    collapsed.then(|| output.join(" "))
+}
+
+// RMS of a WAV payload, or None if it isn't a WAV we can read
+fn wav_rms(audio_bytes: &[u8]) -> Option<f32> {
+    let mut reader = hound::WavReader::new(Cursor::new(audio_bytes)).ok()?;
+    let spec = reader.spec();
+
+    let mut sum_squares = 0.0f64;
+    let mut count = 0usize;
+    match spec.sample_format {
+        hound::SampleFormat::Float => {
+            for sample in reader.samples::<f32>().flatten() {
+                sum_squares += (sample as f64) * (sample as f64);
+                count += 1;
+            }
+        }
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample.saturating_sub(1))) as f64;
+            for sample in reader.samples::<i32>().flatten() {
+                let value = sample as f64 / scale;
+                sum_squares += value * value;
+                count += 1;
+            }
+        }
+    }
+
+    if count == 0 {
+        return None;
+    }
+    Some((sum_squares / count as f64).sqrt() as f32)
+}
+
+fn is_auth_error(error: &str) -> bool {
+    error.contains("returned 401") || error.contains("returned 403")
+}
+
+pub(crate) fn wav_duration_seconds(audio_bytes: &[u8]) -> Option<f64> {
+    let reader = hound::WavReader::new(Cursor::new(audio_bytes)).ok()?;
+    let sample_rate = reader.spec().sample_rate;
+    (sample_rate > 0).then(|| reader.duration() as f64 / sample_rate as f64)
+}
+
+// Drops cached provider configs and fetches the selected model's again
+#[tauri::command]
+pub async fn refresh_api_response_config(app: AppHandle) -> Result<(), String> {
+    api_config_cache::clear(&app);
+
+    let (_, _, selected_model) = get_stored_credentials(&app).await?;
+    let (provider, model) = selected_model.map_or((None, None), |m| (Some(m.provider), Some(m.model)));
+    fetch_api_response_config(&app, provider, model).await?;
+    Ok(())
+}
+
+// API response configuration, from the cache while it's fresh
 async fn fetch_api_response_config(
     app: &AppHandle,
     provider: Option<String>,
     model: Option<String>,
+) -> Result<ApiResponseConfig, String> {
+    if let Some(config) = api_config_cache::get(app, provider.as_deref(), model.as_deref()).await {
+        return Ok(config);
+    }
+
+    let config = request_api_response_config(app, provider.clone(), model.clone()).await?;
+    api_config_cache::store(app, provider.as_deref(), model.as_deref(), &config).await;
+    Ok(config)
+}
+
+// Helper function to fetch API response configuration
+async fn request_api_response_config(
+    app: &AppHandle,
+    provider: Option<String>,
+    model: Option<String>,
 ) -> Result<ApiResponseConfig, String> {
     // Get environment variables
     let app_endpoint = get_app_endpoint()?;
@@ -278,26 +950,28 @@ async fn fetch_api_response_config(
     let (license_key, instance_id, _) = get_stored_credentials(app).await?;
 
     // Make HTTP request to response endpoint
-    let client = reqwest::Client::new();
     let url = format!("{}/api/response", app_endpoint);
 
-    let mut request = client
-        .get(&url)
-        .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", api_access_key))
-        .header("license_key", &license_key)
-        .header("instance", &instance_id)
-        .header("machine_id", &machine_id);
-
-    // Add optional headers
-    if let Some(p) = provider {
-        request = request.header("provider", p);
-    }
-    if let Some(m) = model {
-        request = request.header("model", m);
-    }
-
-    let response = request.send().await.map_err(|e| {
+    let response = http::send(app, EndpointKind::AppApi, &http::provider_key(&url), |client| {
+        let mut request = client
+            .get(&url)
+            .header("Content-Type", "application/json")
+            .header("Authorization", format!("Bearer {}", api_access_key))
+            .header("license_key", &license_key)
+            .header("instance", &instance_id)
+            .header("machine_id", &machine_id);
+
+        // Add optional headers
+        if let Some(p) = provider.as_ref() {
+            request = request.header("provider", p);
+        }
+        if let Some(m) = model.as_ref() {
+            request = request.header("model", m);
+        }
+        Ok(request)
+    })
+    .await
+    .map_err(|e| {
         let error_msg = format!("{}", e);
         if error_msg.contains("url (") {
             let parts: Vec<&str> = error_msg.split(" for url (").collect();
@@ -362,7 +1036,7 @@ fn map_api_error_message(error_rules: &[ApiConfigError], sources: &[String]) ->
         })
 }
 
-fn decode_audio_base64(audio_base64: &str) -> Result<Vec<u8>, String> {
+pub(crate) fn decode_audio_base64(audio_base64: &str) -> Result<Vec<u8>, String> {
     let trimmed = audio_base64.trim();
     let base64_str = if let Some(idx) = trimmed.find(',') {
         &trimmed[idx + 1..]
@@ -376,13 +1050,52 @@ fn decode_audio_base64(audio_base64: &str) -> Result<Vec<u8>, String> {
 }
 
 async fn perform_user_audio_transcription(
-    client: &reqwest::Client,
+    app: &AppHandle,
     url: &str,
     token: &str,
     model: &str,
     headers: Option<&Vec<UserAudioHeader>>,
     audio_bytes: &[u8],
-) -> Result<String, String> {
+    options: &TranscriptionOptions,
+) -> Result<TranscriptionResult, String> {
+    // Multipart forms can't be cloned, so each retry rebuilds its own
+    let response = http::send(app, EndpointKind::Transcription, &http::provider_key(url), |client| {
+        let form = build_transcription_form(model, headers, audio_bytes, options)?;
+        Ok(client.post(url).bearer_auth(token).multipart(form))
+    })
+    .await
+    .map_err(|e| format!("Transcription request failed to send: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "Unable to read transcription error response".to_string());
+        return Err(format!(
+            "Transcription request returned {} with body: {}",
+            status, error_text
+        ));
+    }
+
+    let body_text = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read transcription response: {}", e))?;
+
+    if body_text.trim().is_empty() {
+        return Err("Transcription response was empty".to_string());
+    }
+
+    Ok(parse_transcription_response(&body_text))
+}
+
+fn build_transcription_form(
+    model: &str,
+    headers: Option<&Vec<UserAudioHeader>>,
+    audio_bytes: &[u8],
+    options: &TranscriptionOptions,
+) -> Result<Form, String> {
     let audio_part = Part::bytes(audio_bytes.to_vec())
         .file_name("audio.wav")
         .mime_str("audio/wav")
@@ -392,6 +1105,8 @@ async fn perform_user_audio_transcription(
         .part("file", audio_part)
         .text("model", model.to_string());
 
+    let mut has_response_format = false;
+    let mut has_prompt = false;
     if let Some(extra_headers) = headers {
         for header in extra_headers {
             let key = header.key.trim();
@@ -399,56 +1114,125 @@ async fn perform_user_audio_transcription(
                 continue;
             }
 
+            has_response_format |= key == "response_format";
+            has_prompt |= key == "prompt";
             form = form.text(key.to_string(), header.value.clone());
         }
     }
 
-    let response = client
-        .post(url)
-        .bearer_auth(token)
-        .multipart(form)
-        .send()
-        .await
-        .map_err(|e| format!("Transcription request failed to send: {}", e))?;
-
-    if !response.status().is_success() {
-        let status = response.status();
-        let error_text = response
-            .text()
-            .await
-            .unwrap_or_else(|_| "Unable to read transcription error response".to_string());
-        return Err(format!(
-            "Transcription request returned {} with body: {}",
-            status, error_text
-        ));
+    // Workspace-configured fields win over our own additions
+    if let Some(prompt) = options.prompt.as_ref().filter(|_| !has_prompt) {
+        form = form.text("prompt", prompt.clone());
+    }
+    if options.verbose && !has_response_format {
+        form = form
+            .text("response_format", "verbose_json")
+            .text("timestamp_granularities[]", "segment")
+            .text("timestamp_granularities[]", "word");
     }
 
-    let body_text = response
-        .text()
-        .await
-        .map_err(|e| format!("Failed to read transcription response: {}", e))?;
+    Ok(form)
+}
 
-    if body_text.trim().is_empty() {
-        return Err("Transcription response was empty".to_string());
-    }
+pub(crate) fn parse_transcription_response(body_text: &str) -> TranscriptionResult {
+    let Ok(json) = serde_json::from_str::<serde_json::Value>(body_text) else {
+        return TranscriptionResult {
+            text: body_text.to_string(),
+            ..Default::default()
+        };
+    };
 
-    if let Ok(json) = serde_json::from_str::<serde_json::Value>(&body_text) {
-        if let Some(text) = json.get("text").and_then(|value| value.as_str()) {
-            return Ok(text.to_string());
-        }
+    let text = json
+        .get("text")
+        .and_then(|value| value.as_str())
+        .or_else(|| json.get("transcription").and_then(|value| value.as_str()))
+        .or_else(|| json.get("result").and_then(|value| value.as_str()))
+        .map(|text| text.to_string())
+        .unwrap_or_else(|| json.to_string());
+
+    let segments: Vec<TranscriptionSegment> = json
+        .get("segments")
+        .and_then(|value| value.as_array())
+        .map(|segments| {
+            segments
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, segment)| parse_transcription_segment(idx, segment))
+                .collect()
+        })
+        .unwrap_or_default();
 
-        if let Some(text) = json
-            .get("transcription")
-            .and_then(|value| value.as_str())
-            .or_else(|| json.get("result").and_then(|value| value.as_str()))
-        {
-            return Ok(text.to_string());
+    // Words are either top-level (OpenAI/Groq) or nested inside each segment
+    let mut words: Vec<TranscriptionWord> = json
+        .get("words")
+        .and_then(|value| value.as_array())
+        .map(|words| words.iter().filter_map(parse_transcription_word).collect())
+        .unwrap_or_default();
+    if words.is_empty() {
+        if let Some(raw_segments) = json.get("segments").and_then(|value| value.as_array()) {
+            words = raw_segments
+                .iter()
+                .filter_map(|segment| segment.get("words").and_then(|w| w.as_array()))
+                .flatten()
+                .filter_map(parse_transcription_word)
+                .collect();
         }
+    }
 
-        return Ok(json.to_string());
+    TranscriptionResult {
+        text,
+        language: json
+            .get("language")
+            .and_then(|value| value.as_str())
+            .map(|language| language.to_string()),
+        duration: json.get("duration").and_then(|value| value.as_f64()),
+        segments,
+        words: if words.is_empty() { None } else { Some(words) },
+        ..Default::default()
     }
+}
+
+fn parse_transcription_segment(
+    idx: usize,
+    segment: &serde_json::Value,
+) -> Option<TranscriptionSegment> {
+    let text = segment.get("text").and_then(|value| value.as_str())?;
+    let avg_logprob = segment.get("avg_logprob").and_then(|value| value.as_f64());
+
+    Some(TranscriptionSegment {
+        id: segment
+            .get("id")
+            .and_then(|value| value.as_u64())
+            .map(|id| id as usize)
+            .unwrap_or(idx),
+        start: segment.get("start").and_then(|value| value.as_f64()).unwrap_or(0.0),
+        end: segment.get("end").and_then(|value| value.as_f64()).unwrap_or(0.0),
+        text: text.to_string(),
+        // Explicit confidence if the provider sends one, otherwise derived from avg_logprob
+        confidence: segment
+            .get("confidence")
+            .and_then(|value| value.as_f64())
+            .or_else(|| avg_logprob.map(|logprob| logprob.exp().clamp(0.0, 1.0))),
+        avg_logprob,
+        no_speech_prob: segment.get("no_speech_prob").and_then(|value| value.as_f64()),
+    })
+}
 
-    Ok(body_text)
+fn parse_transcription_word(word: &serde_json::Value) -> Option<TranscriptionWord> {
+    let text = word
+        .get("word")
+        .or_else(|| word.get("text"))
+        .and_then(|value| value.as_str())?;
+
+    Some(TranscriptionWord {
+        word: text.to_string(),
+        start: word.get("start").and_then(|value| value.as_f64()).unwrap_or(0.0),
+        end: word.get("end").and_then(|value| value.as_f64()).unwrap_or(0.0),
+        confidence: word
+            .get("confidence")
+            .or_else(|| word.get("probability"))
+            .and_then(|value| value.as_f64()),
+    })
 }
 
 #[tauri::command]
@@ -458,127 +1242,441 @@ pub async fn chat_stream_response(
     system_prompt: Option<String>,
     image_base64: Option<serde_json::Value>,
     history: Option<String>,
+    request_id: Option<String>,
+    options: Option<ChatStreamOptions>,
 ) -> Result<String, String> {
-    // Get stored credentials to get selected model
-    let (_, _, selected_model) = get_stored_credentials(&app).await?;
-    let (provider, model) = selected_model.as_ref().map_or((None, None), |m| {
-        (Some(m.provider.clone()), Some(m.model.clone()))
-    });
+    let request_id = request_id.unwrap_or_else(|| Uuid::new_v4().to_string());
+    let (_stream_guard, mut abort_rx) = register_chat_stream(&app, &request_id)?;
+    let options = options.unwrap_or_default();
+    let system_prompt = system_prompt
+        .map(|template| prompt_templates::render(&app, &template))
+        .transpose()?;
+
+    // Collect the provider-neutral request; the adapter shapes it for the wire
+    let mut chat_input = ChatInput {
+        system_prompt,
+        user_message,
+        tools: options.tools,
+        reasoning_effort: options.reasoning_effort,
+        response_format: options.response_format,
+        ..Default::default()
+    };
+    if let Some(format) = chat_input.response_format.as_ref() {
+        structured_output::check_schema(format)?;
+    }
+    if let Some(key) = options
+        .request_options
+        .keys()
+        .find(|key| RESERVED_REQUEST_OPTIONS.contains(&key.as_str()))
+    {
+        return Err(format!(
+            "Invalid request option {:?}: must not replace a field the app builds",
+            key
+        ));
+    }
 
-    // Fetch API configuration
-    let api_config = fetch_api_response_config(&app, provider.clone(), model.clone()).await?;
+    // Caller-defined tools with the same name take precedence over built-ins
+    let builtin_tools = options.builtin_tools;
+    if builtin_tools {
+        let defined: Vec<String> = chat_input.tools.iter().filter_map(tool_name).collect();
+        chat_input.tools.extend(
+            chat_tools::builtin_definitions()
+                .into_iter()
+                .filter(|tool| tool_name(tool).is_some_and(|name| !defined.contains(&name))),
+        );
+    }
 
-    // Parse the body from API config to merge with our request
-    let mut extra_body: serde_json::Value = if !api_config.body.is_empty() {
-        serde_json::from_str(&api_config.body).unwrap_or_else(|_| serde_json::json!({}))
-    } else {
-        serde_json::json!({})
+    chat_input.history = parse_history(history);
+    chat_input.images =
+        image_pipeline::prepare_images(&app, &request_id, raw_images(image_base64)).await;
+
+    // On-device model: nothing leaves the machine
+    if let Some(embedded) = embedded_llm::active_config(&app) {
+        let model = embedded.model_file.clone().unwrap_or_default();
+        context_window::fit_to_context(
+            &app,
+            &request_id,
+            &mut chat_input,
+            &model,
+            embedded.context_length,
+        );
+        return run_embedded_chat(&app, &request_id, &embedded, &mut chat_input, &mut abort_rx).await;
+    }
+
+    // Local budgets only cover remote models
+    let estimated_tokens = context_window::estimate_input_tokens(&chat_input) as u64;
+    usage_budget::check(&app, BudgetRequest::Chat { estimated_tokens }).await?;
+
+    let (primary, provider, model) = match direct_provider::active_config(&app) {
+        // Direct mode skips the license server entirely
+        Some(direct) => (ChatTarget::from(direct), None, None),
+        None => {
+            // Get stored credentials to get selected model
+            let (_, _, selected_model) = get_stored_credentials(&app).await?;
+            let (provider, model) = selected_model.as_ref().map_or((None, None), |m| {
+                (Some(m.provider.clone()), Some(m.model.clone()))
+            });
+
+            // Fetch API configuration
+            let api_config =
+                fetch_api_response_config(&app, provider.clone(), model.clone()).await?;
+            (ChatTarget::from(api_config), provider, model)
+        }
+    };
+    let mut candidate = ChatCandidate::new(primary, provider, model, false);
+    let fallbacks = chat_fallback::active_entries(&app);
+    let mut next_fallback = 0;
+
+    let turn = ChatTurnContext {
+        app: &app,
+        request_id: &request_id,
+        request_options: &options.request_options,
     };
 
-    // Build messages array in OpenAI format
-    let mut messages: Vec<serde_json::Value> = Vec::new();
+    // Text from every round; a tool round's preamble is streamed to the UI too
+    let mut full_response = String::new();
+    let mut reasoning = String::new();
+    let mut tool_round = 0;
+    let mut format_retried = false;
+    let mut json = None;
+    loop {
+        let round_start = full_response.len();
+        let outcome = loop {
+            // Re-checked per attempt: a fallback model may have a smaller window
+            context_window::fit(&app, &request_id, &mut chat_input, &candidate.target.model);
+            let attempt = stream_chat_turn(
+                &turn,
+                &candidate,
+                &chat_input,
+                &mut abort_rx,
+                &mut full_response,
+                &mut reasoning,
+            )
+            .await;
+            match attempt {
+                Ok(outcome) => break outcome,
+                Err(error) if error.retryable => {
+                    match next_fallback_candidate(&app, &fallbacks, &mut next_fallback).await {
+                        Some(next) => {
+                            tracing::warn!(
+                                "Chat model {} failed, trying {}: {}",
+                                candidate.target.model,
+                                next.target.model,
+                                error.message
+                            );
+                            candidate = next;
+                        }
+                        None => return Err(error.message),
+                    }
+                }
+                Err(error) => return Err(error.message),
+            }
+        };
+        let turn_calls = match outcome {
+            ChatTurn::Finished(turn_calls, _) => turn_calls,
+            ChatTurn::Cancelled => {
+                return Ok(emit_chat_stream_cancelled(&app, &request_id, full_response, reasoning));
+            }
+        };
+        let tool_calls = &turn_calls.calls;
+        if tool_calls.is_empty() {
+            let answer = full_response[round_start..].to_string();
+            match check_response_format(&app, &request_id, &mut chat_input, &answer, &mut format_retried)? {
+                FormatCheck::Accepted(parsed) => {
+                    json = parsed;
+                    break;
+                }
+                FormatCheck::Retry => {
+                    full_response.truncate(round_start);
+                    continue;
+                }
+            }
+        }
 
-    // Add system message if provided
-    if let Some(sys_prompt) = system_prompt {
-        messages.push(serde_json::json!({
-            "role": "system",
-            "content": sys_prompt
-        }));
+        // Only built-in tools are run here; anything else ends the turn and
+        // the frontend answers it in a new request
+        let run_here = builtin_tools
+            && tool_round < chat_tools::MAX_TOOL_ROUNDS
+            && tool_calls.iter().all(|call| chat_tools::is_builtin(&call.name));
+        for call in tool_calls {
+            let _ = app.emit(
+                "chat_tool_call",
+                ChatToolCallEvent::new(&request_id, call, run_here),
+            );
+        }
+        if !run_here {
+            break;
+        }
+        tool_round += 1;
+
+        chat_input.commit_user_turn();
+        chat_input.push_assistant_tool_calls(&full_response[round_start..], &turn_calls);
+        let mut images = Vec::new();
+        for call in tool_calls {
+            let output = tokio::select! {
+                _ = &mut abort_rx => {
+                    return Ok(emit_chat_stream_cancelled(&app, &request_id, full_response, reasoning));
+                }
+                output = chat_tools::run_builtin(&app, &request_id, call) => output,
+            };
+            chat_input.push_tool_result(call, output.text);
+            images.extend(output.image);
+        }
+        if !images.is_empty() {
+            chat_input.push_user_images("Screenshot from the take_screenshot tool.", &images);
+        }
     }
 
-    // Add history if provided
-    if let Some(history_str) = history {
-        if let Ok(history_messages) = serde_json::from_str::<Vec<serde_json::Value>>(&history_str) {
-            messages.extend(history_messages);
+    // Emit completion event
+    let _ = app.emit(
+        "chat_stream_complete",
+        ChatStreamComplete {
+            request_id: request_id.clone(),
+            text: full_response.clone(),
+            reasoning: Some(reasoning).filter(|reasoning| !reasoning.is_empty()),
+            model: Some(candidate.target.model.clone()),
+            json,
+        },
+    );
+
+    Ok(full_response)
+}
+
+enum FormatCheck {
+    // Parsed answer; None when no format was requested
+    Accepted(Option<serde_json::Value>),
+    // The correction was added to the input, run another turn
+    Retry,
+}
+
+// Validates the final answer against the request's response_format. The
+// first invalid answer goes back to the model with the errors, once.
+fn check_response_format(
+    app: &AppHandle,
+    request_id: &str,
+    chat_input: &mut ChatInput,
+    answer: &str,
+    retried: &mut bool,
+) -> Result<FormatCheck, String> {
+    let Some(format) = chat_input.response_format.as_ref() else {
+        return Ok(FormatCheck::Accepted(None));
+    };
+
+    match structured_output::validate(format, answer) {
+        Ok(parsed) => Ok(FormatCheck::Accepted(Some(parsed))),
+        Err(errors) if !*retried => {
+            *retried = true;
+            tracing::warn!("Answer did not match the response format, retrying: {}", errors.join("; "));
+            let feedback = structured_output::correction_prompt(&errors);
+            let _ = app.emit(
+                "chat_stream_format_retry",
+                ChatStreamFormatRetry {
+                    request_id: request_id.to_string(),
+                    errors,
+                },
+            );
+            chat_input.commit_user_turn();
+            chat_input.push_retry_feedback(answer, &feedback);
+            Ok(FormatCheck::Retry)
         }
+        Err(errors) => Err(format!(
+            "The answer did not match the requested format: {}",
+            errors.join("; ")
+        )),
     }
+}
 
-    // Build user message content
-    let mut user_content: Vec<serde_json::Value> = Vec::new();
+// Chat history as a JSON array string; anything unparsable is ignored
+fn parse_history(history: Option<String>) -> Vec<serde_json::Value> {
+    history
+        .and_then(|history_str| serde_json::from_str(&history_str).ok())
+        .unwrap_or_default()
+}
 
-    // Add text content
-    user_content.push(serde_json::json!({
-        "type": "text",
-        "text": user_message
-    }));
+// A single base64 string or an array of them; downscaled and re-encoded for
+// upload by image_pipeline
+fn raw_images(image_base64: Option<serde_json::Value>) -> Vec<String> {
+    match image_base64 {
+        Some(serde_json::Value::String(image)) => vec![image],
+        Some(serde_json::Value::Array(images)) => images
+            .iter()
+            .filter_map(|image| image.as_str())
+            .map(|image| image.to_string())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
 
-    // Add image content if provided
-    if let Some(image_data) = image_base64 {
-        if image_data.is_string() {
-            // Single image
-            user_content.push(serde_json::json!({
-                "type": "image_url",
-                "image_url": {
-                    "url": format!("data:image/jpeg;base64,{}", image_data.as_str().unwrap())
-                }
-            }));
-        } else if image_data.is_array() {
-            // Multiple images
-            if let Some(images) = image_data.as_array() {
-                for image in images {
-                    if let Some(img_str) = image.as_str() {
-                        user_content.push(serde_json::json!({
-                            "type": "image_url",
-                            "image_url": {
-                                "url": format!("data:image/jpeg;base64,{}", img_str)
-                            }
-                        }));
-                    }
-                }
-            }
+fn tool_name(tool: &serde_json::Value) -> Option<String> {
+    tool.pointer("/function/name")
+        .and_then(|name| name.as_str())
+        .map(|name| name.to_string())
+}
+
+// Everything about a chat request that stays fixed across tool rounds and fallbacks
+struct ChatTurnContext<'a> {
+    app: &'a AppHandle,
+    request_id: &'a str,
+    request_options: &'a serde_json::Map<String, serde_json::Value>,
+}
+
+// A model the request can be sent to: the primary or a fallback entry
+struct ChatCandidate {
+    target: ChatTarget,
+    // Parsed `body` from the model config, merged into every request
+    extra_body: serde_json::Value,
+    // License-server provider/model, used in error reports
+    provider: Option<String>,
+    model: Option<String>,
+    fallback: bool,
+}
+
+impl ChatCandidate {
+    fn new(
+        target: ChatTarget,
+        provider: Option<String>,
+        model: Option<String>,
+        fallback: bool,
+    ) -> Self {
+        // Parse the body from API config to merge with our request
+        let extra_body = if !target.body.is_empty() {
+            serde_json::from_str(&target.body).unwrap_or_else(|_| serde_json::json!({}))
+        } else {
+            serde_json::json!({})
+        };
+
+        Self {
+            target,
+            extra_body,
+            provider,
+            model,
+            fallback,
         }
     }
+}
 
-    // Add user message
-    messages.push(serde_json::json!({
-        "role": "user",
-        "content": user_content
-    }));
+// Resolves fallback entries in order, skipping any whose config can't be fetched
+async fn next_fallback_candidate(
+    app: &AppHandle,
+    entries: &[ChatFallbackEntry],
+    next: &mut usize,
+) -> Option<ChatCandidate> {
+    while let Some(entry) = entries.get(*next) {
+        *next += 1;
+
+        match resolve_candidate(app, entry, true).await {
+            Ok(candidate) => return Some(candidate),
+            Err(e) => tracing::warn!("Skipping chat fallback {}: {}", entry.label(), e),
+        }
+    }
+    None
+}
 
-    // Build request body
-    let mut request_body = serde_json::json!({
-        "model": api_config.model,
-        "messages": messages,
-        "stream": true
-    });
+async fn resolve_candidate(
+    app: &AppHandle,
+    entry: &ChatFallbackEntry,
+    fallback: bool,
+) -> Result<ChatCandidate, String> {
+    match entry {
+        ChatFallbackEntry::Hosted { provider, model } => {
+            fetch_api_response_config(app, Some(provider.clone()), Some(model.clone()))
+                .await
+                .map(|api_config| {
+                    ChatCandidate::new(
+                        ChatTarget::from(api_config),
+                        Some(provider.clone()),
+                        Some(model.clone()),
+                        fallback,
+                    )
+                })
+        }
+        ChatFallbackEntry::Direct { .. } => entry
+            .direct_config()
+            .map(|direct| ChatCandidate::new(ChatTarget::from(direct), None, None, fallback))
+            .ok_or_else(|| "Invalid direct model entry".to_string()),
+    }
+}
 
-    // Merge extra body parameters from API config
-    if let Some(extra_obj) = extra_body.as_object_mut() {
-        if let Some(req_obj) = request_body.as_object_mut() {
-            for (key, value) in extra_obj.iter() {
-                req_obj.insert(key.clone(), value.clone());
-            }
+enum ChatTurn {
+    Finished(ToolCalls, ChatTurnUsage),
+    Cancelled,
+}
+
+// What one request cost, as recorded in the usage ledger
+#[derive(Debug, Clone, Copy)]
+struct ChatTurnUsage {
+    prompt_tokens: u64,
+    completion_tokens: u64,
+    latency_ms: u64,
+    estimated: bool,
+}
+
+struct ChatTurnError {
+    // Already mapped through the config's error rules
+    message: String,
+    // Connection error, 5xx, rate limit or a stream dying before its first
+    // token: worth retrying on the next fallback model
+    retryable: bool,
+}
+
+// Sends one request and streams its answer, appending content to
+// `full_response` and thinking text to `reasoning`
+async fn stream_chat_turn(
+    turn: &ChatTurnContext<'_>,
+    candidate: &ChatCandidate,
+    input: &ChatInput,
+    abort_rx: &mut oneshot::Receiver<()>,
+    full_response: &mut String,
+    reasoning: &mut String,
+) -> Result<ChatTurn, ChatTurnError> {
+    let app = turn.app;
+    let target = &candidate.target;
+    let telemetry = !target.direct;
+    let (model, provider) = (&candidate.model, &candidate.provider);
+    let started = Instant::now();
+    let (output_start, reasoning_start) = (full_response.len(), reasoning.len());
+
+    // Build request body
+    let adapter = target.provider_type;
+    let mut request_body = adapter.build_body(&target.model, input);
+
+    // Merge extra body parameters from API config, then the caller's own options
+    if let Some(req_obj) = request_body.as_object_mut() {
+        let extra_obj = candidate.extra_body.as_object().into_iter().flatten();
+        for (key, value) in extra_obj.chain(turn.request_options) {
+            req_obj.insert(key.clone(), value.clone());
         }
     }
 
     // Make HTTP request to the configured endpoint with streaming
-    let client = reqwest::Client::new();
-    let error_rules = api_config.errors.clone().unwrap_or_default();
-    let response = match client
-        .post(&api_config.url)
-        .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", api_config.user_token))
-        .json(&request_body)
-        .send()
-        .await
-    {
+    let error_rules = target.errors.clone().unwrap_or_default();
+    let chat_url = adapter.stream_url(&target.url, &target.model);
+    let chat_provider = http::provider_key(&chat_url);
+    let send_request = http::send(app, EndpointKind::Chat, &chat_provider, |client| {
+        let request = client
+            .post(&chat_url)
+            .header("Content-Type", "application/json");
+        Ok(adapter
+            .authorize(request, &target.token)
+            .json(&request_body))
+    });
+    let sent = tokio::select! {
+        _ = &mut *abort_rx => return Ok(ChatTurn::Cancelled),
+        sent = send_request => sent,
+    };
+    let response = match sent {
         Ok(resp) => resp,
         Err(e) => {
             let mut sources = vec![e.to_string()];
-            if let Ok(url) = Url::parse(&api_config.url) {
+            if let Ok(url) = Url::parse(&target.url) {
                 sources.push(url.to_string());
             }
             let final_message = map_api_error_message(&error_rules, &sources);
-            tauri::async_runtime::spawn({
-                let app = app.clone();
-                let provider = provider.clone();
-                let model = model.clone();
-                let error_msg = e.to_string();
-                async move {
-                    report_api_error(app, error_msg, "/api/chat".to_string(), model, provider).await;
-                }
+            report_chat_error(app, telemetry, e.to_string(), model, provider);
+            return Err(ChatTurnError {
+                message: final_message,
+                retryable: !matches!(e, HttpError::Build(_)),
             });
-            return Err(final_message);
         }
     };
 
@@ -603,122 +1701,436 @@ pub async fn chat_stream_response(
         }
 
         let final_message = map_api_error_message(&error_rules, &sources);
-        tauri::async_runtime::spawn({
-            let app = app.clone();
-            let provider = provider.clone();
-            let model = model.clone();
-            let error_msg = format!("{}: {}", status, error_text);
-            async move {
-                report_api_error(app, error_msg, "/api/chat".to_string(), model, provider).await;
-            }
+        report_chat_error(app, telemetry, format!("{}: {}", status, error_text), model, provider);
+        // A rejected token may just be a stale cached config
+        if matches!(status.as_u16(), 401 | 403) && !target.direct {
+            api_config_cache::invalidate(app, provider.as_deref(), model.as_deref());
+        }
+        return Err(ChatTurnError {
+            message: final_message,
+            retryable: status.as_u16() == 429 || status.is_server_error(),
         });
-        return Err(final_message);
     }
 
     // Handle streaming response
     let mut stream = response.bytes_stream();
-    let mut full_response = String::new();
-    let mut buffer = String::new();
+    let mut decoder = SseDecoder::new();
+    let mut stream_done = false;
+    let mut done_signalled = false;
+    // Nothing shown to the user yet, so a failure can still fall back
+    let mut answered = false;
+    let mut tool_calls = ToolCallAccumulator::default();
     let mut usage: Option<serde_json::Value> = None;
     let mut activity_reported = false;
     let activity_app = app.clone();
-    let activity_model = api_config.model.clone();
+    let activity_model = target.model.clone();
     let activity_app_version = app.package_info().version.to_string();
 
-    while let Some(chunk) = stream.next().await {
-        match chunk {
-            Ok(bytes) => {
-                let chunk_str = String::from_utf8_lossy(&bytes);
-                buffer.push_str(&chunk_str);
-
-                // Process complete lines
-                let lines: Vec<&str> = buffer.split('\n').collect();
-                let incomplete_line = lines.last().unwrap_or(&"").to_string();
-
-                for line in &lines[..lines.len() - 1] {
-                    // Process all but the last (potentially incomplete) line
-                    let trimmed_line = line.trim();
+    while !stream_done {
+        let next_chunk = tokio::select! {
+            // Dropping the stream closes the connection, so no more tokens are billed
+            _ = &mut *abort_rx => return Ok(ChatTurn::Cancelled),
+            next_chunk = stream.next() => next_chunk,
+        };
 
-                    if trimmed_line.starts_with("data: ") {
-                        let json_str = trimmed_line.strip_prefix("data: ").unwrap_or("");
-
-                        if json_str == "[DONE]" {
-                            break;
-                        }
+        let events = match next_chunk {
+            Some(Ok(bytes)) => decoder.feed(&bytes),
+            Some(Err(e)) => {
+                let sources = vec![e.to_string()];
+                let final_message = map_api_error_message(&error_rules, &sources);
+                report_chat_error(app, telemetry, e.to_string(), model, provider);
+                return Err(ChatTurnError {
+                    message: final_message,
+                    retryable: !answered,
+                });
+            }
+            None => {
+                stream_done = true;
+                decoder.finish().into_iter().collect()
+            }
+        };
 
-                        if !json_str.is_empty() {
-                            // Try to parse the JSON and extract content
-                            if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(json_str)
-                            {
-                                if usage.is_none() {
-                                    if let Some(collected) = parsed.get("usage") {
-                                        if !collected.is_null() {
-                                            usage = Some(collected.clone());
-                                        }
-                                    }
-                                }
-                                if let Some(choices) =
-                                    parsed.get("choices").and_then(|c| c.as_array())
-                                {
-                                    if let Some(first_choice) = choices.first() {
-                                        if let Some(delta) = first_choice.get("delta") {
-                                            if let Some(content) =
-                                                delta.get("content").and_then(|c| c.as_str())
-                                            {
-                                                full_response.push_str(content);
-                                                // Emit just the content to frontend
-                                                let _ = app.emit("chat_stream_chunk", content);
-                                                if !activity_reported {
-                                                    activity_reported = true;
-                                                    tauri::async_runtime::spawn({
-                                                        let activity_app = activity_app.clone();
-                                                        let activity_model = activity_model.clone();
-                                                        let activity_app_version = activity_app_version.clone();
-                                                        let captured_metrics = usage.clone();
-                                                        async move {
-                                                            let _ = user_activity(
-                                                                activity_app,
-                                                                captured_metrics,
-                                                                activity_model,
-                                                                activity_app_version,
-                                                            )
-                                                            .await;
-                                                        }
-                                                    });
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
+        for event in events {
+            let update = adapter.parse_event(&event);
 
-                // Update buffer with incomplete line
-                buffer = incomplete_line;
+            if let Some(error) = update.error {
+                let final_message = map_api_error_message(&error_rules, std::slice::from_ref(&error));
+                report_chat_error(app, telemetry, error, model, provider);
+                return Err(ChatTurnError {
+                    message: final_message,
+                    retryable: !answered,
+                });
             }
-            Err(e) => {
-                let sources = vec![e.to_string()];
-                let final_message = map_api_error_message(&error_rules, &sources);
+            if let Some(collected) = update.usage {
+                chat_adapters::merge_usage(&mut usage, collected);
+            }
+            let has_output = update.content.is_some()
+                || update.reasoning.is_some()
+                || !update.tool_calls.is_empty();
+            if has_output && !answered {
+                answered = true;
+                let _ = app.emit(
+                    "chat_stream_model",
+                    ChatStreamModel {
+                        request_id: turn.request_id.to_string(),
+                        model: target.model.clone(),
+                        fallback: candidate.fallback,
+                    },
+                );
+            }
+            for delta in update.tool_calls {
+                tool_calls.push(delta);
+            }
+            for delta in update.thinking {
+                tool_calls.push_thinking(delta);
+            }
+            if let Some(thinking) = update.reasoning {
+                reasoning.push_str(&thinking);
+                let _ = app.emit(
+                    "chat_stream_reasoning",
+                    ChatStreamReasoning {
+                        request_id: turn.request_id.to_string(),
+                        content: thinking,
+                    },
+                );
+            }
+            if update.done {
+                stream_done = true;
+                done_signalled = true;
+                break;
+            }
+            let Some(content) = update.content else {
+                continue;
+            };
+
+            full_response.push_str(&content);
+            // Emit the content tagged with its request id
+            let _ = app.emit(
+                "chat_stream_chunk",
+                ChatStreamChunk {
+                    request_id: turn.request_id.to_string(),
+                    content,
+                },
+            );
+            if telemetry && !activity_reported {
+                activity_reported = true;
                 tauri::async_runtime::spawn({
-                    let app = app.clone();
-                    let provider = provider.clone();
-                    let model = model.clone();
-                    let error_msg = e.to_string();
+                    let activity_app = activity_app.clone();
+                    let activity_model = activity_model.clone();
+                    let activity_app_version = activity_app_version.clone();
+                    let captured_metrics = usage.clone();
                     async move {
-                        report_api_error(app, error_msg, "/api/chat".to_string(), model, provider).await;
+                        let _ = user_activity(
+                            activity_app,
+                            captured_metrics,
+                            activity_model,
+                            activity_app_version,
+                        )
+                        .await;
                     }
                 });
-                return Err(final_message);
             }
         }
     }
 
-    // Emit completion event
-    let _ = app.emit("chat_stream_complete", &full_response);
+    // Connection closed without a single token or end-of-stream marker
+    if !answered && !done_signalled {
+        report_chat_error(app, telemetry, "Stream closed before the first token".to_string(), model, provider);
+        return Err(ChatTurnError {
+            message: "The model closed the connection without answering. Please try again."
+                .to_string(),
+            retryable: true,
+        });
+    }
 
-    Ok(full_response)
+    let tool_calls = tool_calls.finish();
+    let output_tokens = context_window::text_tokens(&full_response[output_start..])
+        + context_window::text_tokens(&reasoning[reasoning_start..])
+        + tool_calls
+            .calls
+            .iter()
+            .map(|call| context_window::text_tokens(&call.arguments))
+            .sum::<usize>();
+    let usage = record_chat_usage(turn, candidate, input, usage.as_ref(), output_tokens, started);
+
+    Ok(ChatTurn::Finished(tool_calls, usage))
+}
+
+// Providers that report no usage (or only part of it) are logged with
+// estimates, so local budgets and cost totals still count them
+fn record_chat_usage(
+    turn: &ChatTurnContext<'_>,
+    candidate: &ChatCandidate,
+    input: &ChatInput,
+    usage: Option<&serde_json::Value>,
+    output_tokens: usize,
+    started: Instant,
+) -> ChatTurnUsage {
+    let reported = |key: &str| usage.and_then(|usage| usage.get(key)).and_then(|v| v.as_u64());
+    let (prompt_tokens, completion_tokens) = (reported("prompt_tokens"), reported("completion_tokens"));
+    let usage = ChatTurnUsage {
+        prompt_tokens: prompt_tokens
+            .unwrap_or_else(|| context_window::estimate_input_tokens(input) as u64),
+        completion_tokens: completion_tokens.unwrap_or(output_tokens as u64),
+        latency_ms: started.elapsed().as_millis() as u64,
+        estimated: prompt_tokens.is_none() || completion_tokens.is_none(),
+    };
+
+    usage_ledger::record(
+        turn.app,
+        UsageRecord {
+            kind: UsageKind::Chat,
+            request_id: Some(turn.request_id.to_string()),
+            provider: candidate
+                .provider
+                .clone()
+                .or_else(|| candidate.target.direct.then(|| "direct".to_string())),
+            model: candidate.target.model.clone(),
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            audio_seconds: 0.0,
+            latency_ms: usage.latency_ms,
+            estimated: usage.estimated,
+        },
+    );
+    usage
+}
+
+async fn run_embedded_chat(
+    app: &AppHandle,
+    request_id: &str,
+    config: &EmbeddedLlmConfig,
+    chat_input: &mut ChatInput,
+    abort_rx: &mut oneshot::Receiver<()>,
+) -> Result<String, String> {
+    let on_chunk = |content: String| {
+        let _ = app.emit(
+            "chat_stream_chunk",
+            ChatStreamChunk {
+                request_id: request_id.to_string(),
+                content,
+            },
+        );
+    };
+
+    let mut format_retried = false;
+    loop {
+        match embedded_llm::stream_chat(app, config, chat_input, abort_rx, on_chunk).await? {
+            EmbeddedOutcome::Complete(full_response) => {
+                let json = match check_response_format(
+                    app,
+                    request_id,
+                    chat_input,
+                    &full_response,
+                    &mut format_retried,
+                )? {
+                    FormatCheck::Accepted(parsed) => parsed,
+                    FormatCheck::Retry => continue,
+                };
+                let _ = app.emit(
+                    "chat_stream_complete",
+                    ChatStreamComplete {
+                        request_id: request_id.to_string(),
+                        text: full_response.clone(),
+                        reasoning: None,
+                        model: config.model_file.clone(),
+                        json,
+                    },
+                );
+                return Ok(full_response);
+            }
+            EmbeddedOutcome::Cancelled(partial) => {
+                return Ok(emit_chat_stream_cancelled(app, request_id, partial, String::new()));
+            }
+        }
+    }
+}
+
+fn report_chat_error(
+    app: &AppHandle,
+    telemetry: bool,
+    error_msg: String,
+    model: &Option<String>,
+    provider: &Option<String>,
+) {
+    // Direct mode errors stay on this machine
+    if !telemetry {
+        return;
+    }
+
+    tauri::async_runtime::spawn({
+        let app = app.clone();
+        let provider = provider.clone();
+        let model = model.clone();
+        async move {
+            report_api_error(app, error_msg, "/api/chat".to_string(), model, provider).await;
+        }
+    });
+}
+
+// Most models a single comparison may fan out to
+const MAX_COMPARE_MODELS: usize = 4;
+
+// One model's answer in a chat_compare
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatCompareResult {
+    // `{request_id}:{index}`, the id this model's stream events carry
+    pub request_id: String,
+    pub model: String,
+    pub text: String,
+    pub reasoning: Option<String>,
+    // Set when this model failed; the others are unaffected
+    pub error: Option<String>,
+    pub cancelled: bool,
+    // Until the answer finished (or failed)
+    pub latency_ms: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    // Token counts are local estimates because the provider reported none
+    pub estimated: bool,
+}
+
+// Sends one prompt to several models at once so their answers can be put side
+// by side. Each model streams under `{request_id}:{index}` and can be
+// cancelled by that id; results come back in the order of `models`. No
+// tools or fallbacks: every answer comes from the model it is labelled with.
+#[tauri::command]
+pub async fn chat_compare(
+    app: AppHandle,
+    models: Vec<ChatFallbackEntry>,
+    user_message: String,
+    system_prompt: Option<String>,
+    image_base64: Option<serde_json::Value>,
+    history: Option<String>,
+    request_id: Option<String>,
+) -> Result<Vec<ChatCompareResult>, String> {
+    if models.is_empty() || models.len() > MAX_COMPARE_MODELS {
+        return Err(format!(
+            "Invalid models: must list between 1 and {} models",
+            MAX_COMPARE_MODELS
+        ));
+    }
+    let request_id = request_id.unwrap_or_else(|| Uuid::new_v4().to_string());
+    let system_prompt = system_prompt
+        .map(|template| prompt_templates::render(&app, &template))
+        .transpose()?;
+
+    let mut chat_input = ChatInput {
+        system_prompt,
+        user_message,
+        history: parse_history(history),
+        ..Default::default()
+    };
+    // Images are prepared once and shared by every model
+    chat_input.images =
+        image_pipeline::prepare_images(&app, &request_id, raw_images(image_base64)).await;
+
+    // Every model is sent the same input, so the daily token budget is
+    // checked once for all of them; each still counts as a request per minute
+    let estimated_tokens = context_window::estimate_input_tokens(&chat_input) as u64;
+    usage_budget::check(
+        &app,
+        BudgetRequest::Chat {
+            estimated_tokens: estimated_tokens * models.len() as u64,
+        },
+    )
+    .await?;
+    for _ in 1..models.len() {
+        usage_budget::check_rate(&app)?;
+    }
+
+    let runs = models.iter().enumerate().map(|(index, entry)| {
+        compare_model(&app, format!("{}:{}", request_id, index), entry, chat_input.clone())
+    });
+    Ok(join_all(runs).await)
+}
+
+async fn compare_model(
+    app: &AppHandle,
+    request_id: String,
+    entry: &ChatFallbackEntry,
+    mut chat_input: ChatInput,
+) -> ChatCompareResult {
+    let started = Instant::now();
+    let mut text = String::new();
+    let mut reasoning = String::new();
+    let no_options = serde_json::Map::new();
+
+    let outcome = async {
+        let (_stream_guard, mut abort_rx) = register_chat_stream(app, &request_id)?;
+        let candidate = resolve_candidate(app, entry, false).await?;
+        context_window::fit(app, &request_id, &mut chat_input, &candidate.target.model);
+        let turn = ChatTurnContext {
+            app,
+            request_id: &request_id,
+            request_options: &no_options,
+        };
+        stream_chat_turn(&turn, &candidate, &chat_input, &mut abort_rx, &mut text, &mut reasoning)
+            .await
+            .map(|outcome| (outcome, candidate.target.model.clone()))
+            .map_err(|error| error.message)
+    }
+    .await;
+
+    let mut result = ChatCompareResult {
+        request_id: request_id.clone(),
+        model: entry.label(),
+        text: String::new(),
+        reasoning: None,
+        error: None,
+        cancelled: false,
+        latency_ms: started.elapsed().as_millis() as u64,
+        prompt_tokens: 0,
+        completion_tokens: 0,
+        estimated: false,
+    };
+    match outcome {
+        Ok((ChatTurn::Finished(_, usage), model)) => {
+            let reasoning = Some(reasoning).filter(|reasoning| !reasoning.is_empty());
+            let _ = app.emit(
+                "chat_stream_complete",
+                ChatStreamComplete {
+                    request_id,
+                    text: text.clone(),
+                    reasoning: reasoning.clone(),
+                    model: Some(model),
+                    json: None,
+                },
+            );
+            result.text = text;
+            result.reasoning = reasoning;
+            result.latency_ms = usage.latency_ms;
+            result.prompt_tokens = usage.prompt_tokens;
+            result.completion_tokens = usage.completion_tokens;
+            result.estimated = usage.estimated;
+        }
+        Ok((ChatTurn::Cancelled, _)) => {
+            result.reasoning = Some(reasoning.clone()).filter(|reasoning| !reasoning.is_empty());
+            result.text = emit_chat_stream_cancelled(app, &request_id, text, reasoning);
+            result.cancelled = true;
+        }
+        Err(error) => {
+            tracing::warn!("Chat compare model {} failed: {}", entry.label(), error);
+            result.error = Some(error);
+        }
+    }
+    result
+}
+
+// Returns false if the stream already finished (or never existed)
+#[tauri::command]
+pub async fn cancel_chat_stream(app: AppHandle, request_id: String) -> Result<bool, String> {
+    let state = app.state::<ChatStreamState>();
+    let abort_tx = state
+        .streams
+        .lock()
+        .map_err(|e| format!("Failed to cancel chat stream: {}", e))?
+        .remove(&request_id);
+
+    Ok(match abort_tx {
+        Some(abort_tx) => abort_tx.send(()).is_ok(),
+        None => false,
+    })
 }
 
 async fn user_activity(
@@ -774,15 +2186,15 @@ async fn user_activity(
     }
 
     let activity_url = format!("{}/api/activity", app_endpoint.trim_end_matches('/'));
-    let client = reqwest::Client::new();
 
-    let _ = client
-        .post(&activity_url)
-        .header("Authorization", format!("Bearer {}", api_access_key))
-        .header("Content-Type", "application/json")
-        .json(&payload)
-        .send()
-        .await;
+    let _ = http::send(&app, EndpointKind::Telemetry, &http::provider_key(&activity_url), |client| {
+        Ok(client
+            .post(&activity_url)
+            .header("Authorization", format!("Bearer {}", api_access_key))
+            .header("Content-Type", "application/json")
+            .json(&payload))
+    })
+    .await;
 
     Ok(())
 }
@@ -840,17 +2252,17 @@ async fn report_api_error(
     });
 
     let error_url = format!("{}/api/error", app_endpoint.trim_end_matches('/'));
-    let client = reqwest::Client::new();
 
     tracing::debug!("Reporting API error: {:?}", payload);
 
-    if let Err(e) = client
-        .post(&error_url)
-        .header("Authorization", format!("Bearer {}", api_access_key))
-        .header("Content-Type", "application/json")
-        .json(&payload)
-        .send()
-        .await
+    if let Err(e) = http::send(&app, EndpointKind::Telemetry, &http::provider_key(&error_url), |client| {
+        Ok(client
+            .post(&error_url)
+            .header("Authorization", format!("Bearer {}", api_access_key))
+            .header("Content-Type", "application/json")
+            .json(&payload))
+    })
+    .await
     {
         tracing::warn!("Failed to report API error: {}", e);
     }
@@ -858,35 +2270,35 @@ async fn report_api_error(
 
 // Models API Command
 #[tauri::command]
-pub async fn fetch_models() -> Result<Vec<Model>, String> {
+pub async fn fetch_models(app: AppHandle) -> Result<Vec<Model>, String> {
     // Get environment variables
     let app_endpoint = get_app_endpoint()?;
     let api_access_key = get_api_access_key()?;
 
     // Make HTTP request to models endpoint
-    let client = reqwest::Client::new();
     let url = format!("{}/api/models", app_endpoint);
 
-    let response = client
-        .post(&url)
-        .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", api_access_key))
-        .send()
-        .await
-        .map_err(|e| {
-            let error_msg = format!("{}", e);
-            if error_msg.contains("url (") {
-                // Remove the URL part from the error message
-                let parts: Vec<&str> = error_msg.split(" for url (").collect();
-                if parts.len() > 1 {
-                    format!("Failed to make models request: {}", parts[0])
-                } else {
-                    format!("Failed to make models request: {}", error_msg)
-                }
+    let response = http::send(&app, EndpointKind::AppApi, &http::provider_key(&url), |client| {
+        Ok(client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .header("Authorization", format!("Bearer {}", api_access_key)))
+    })
+    .await
+    .map_err(|e| {
+        let error_msg = format!("{}", e);
+        if error_msg.contains("url (") {
+            // Remove the URL part from the error message
+            let parts: Vec<&str> = error_msg.split(" for url (").collect();
+            if parts.len() > 1 {
+                format!("Failed to make models request: {}", parts[0])
             } else {
                 format!("Failed to make models request: {}", error_msg)
             }
-        })?;
+        } else {
+            format!("Failed to make models request: {}", error_msg)
+        }
+    })?;
 
     // Check if the response is successful
     if !response.status().is_success() {
@@ -929,36 +2341,36 @@ pub async fn create_system_prompt(
     let machine_id: String = app.machine_uid().get_machine_uid().unwrap().id.unwrap();
     let app_version: String = app.package_info().version.to_string();
     // Make HTTP request to models endpoint
-    let client = reqwest::Client::new();
     let url = format!("{}/api/prompt", app_endpoint);
 
-    let response = client
-        .post(&url)
-        .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", api_access_key))
-        .header("license_key", &license_key)
-        .header("instance", &instance_id)
-        .header("machine_id", &machine_id)
-        .header("app_version", &app_version)
-        .json(&serde_json::json!({
-            "user_prompt": user_prompt
-        }))
-        .send()
-        .await
-        .map_err(|e| {
-            let error_msg = format!("{}", e);
-            if error_msg.contains("url (") {
-                // Remove the URL part from the error message
-                let parts: Vec<&str> = error_msg.split(" for url (").collect();
-                if parts.len() > 1 {
-                    format!("Failed to make models request: {}", parts[0])
-                } else {
-                    format!("Failed to make models request: {}", error_msg)
-                }
+    let response = http::send(&app, EndpointKind::AppApi, &http::provider_key(&url), |client| {
+        Ok(client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .header("Authorization", format!("Bearer {}", api_access_key))
+            .header("license_key", &license_key)
+            .header("instance", &instance_id)
+            .header("machine_id", &machine_id)
+            .header("app_version", &app_version)
+            .json(&serde_json::json!({
+                "user_prompt": user_prompt
+            })))
+    })
+    .await
+    .map_err(|e| {
+        let error_msg = format!("{}", e);
+        if error_msg.contains("url (") {
+            // Remove the URL part from the error message
+            let parts: Vec<&str> = error_msg.split(" for url (").collect();
+            if parts.len() > 1 {
+                format!("Failed to make models request: {}", parts[0])
             } else {
                 format!("Failed to make models request: {}", error_msg)
             }
-        })?;
+        } else {
+            format!("Failed to make models request: {}", error_msg)
+        }
+    })?;
 
     // Check if the response is successful
     if !response.status().is_success() {
@@ -1016,31 +2428,31 @@ pub async fn get_activity(app: AppHandle) -> Result<serde_json::Value, String> {
 
     let app_version = app.package_info().version.to_string();
 
-    let client = reqwest::Client::new();
     let activity_url = format!("{}/api/activity", app_endpoint.trim_end_matches('/'));
 
-    let response = client
-        .get(&activity_url)
-        .header("Authorization", format!("Bearer {}", api_access_key))
-        .header("license_key", &license_key)
-        .header("instance_name", &instance_id)
-        .header("machine_id", machine_id)
-        .header("app_version", app_version)
-        .send()
-        .await
-        .map_err(|e| {
-            let error_msg = format!("{}", e);
-            if error_msg.contains("url (") {
-                let parts: Vec<&str> = error_msg.split(" for url (").collect();
-                if parts.len() > 1 {
-                    format!("Failed to request activity: {}", parts[0])
-                } else {
-                    format!("Failed to request activity: {}", error_msg)
-                }
+    let response = http::send(&app, EndpointKind::AppApi, &http::provider_key(&activity_url), |client| {
+        Ok(client
+            .get(&activity_url)
+            .header("Authorization", format!("Bearer {}", api_access_key))
+            .header("license_key", &license_key)
+            .header("instance_name", &instance_id)
+            .header("machine_id", &machine_id)
+            .header("app_version", &app_version))
+    })
+    .await
+    .map_err(|e| {
+        let error_msg = format!("{}", e);
+        if error_msg.contains("url (") {
+            let parts: Vec<&str> = error_msg.split(" for url (").collect();
+            if parts.len() > 1 {
+                format!("Failed to request activity: {}", parts[0])
             } else {
                 format!("Failed to request activity: {}", error_msg)
             }
-        })?;
+        } else {
+            format!("Failed to request activity: {}", error_msg)
+        }
+    })?;
 
     if !response.status().is_success() {
         let status = response.status();
@@ -0,0 +1,264 @@
+// Cache of the license server's per-model configuration (`/api/response`),
+// which used to be fetched before every chat and transcription request.
+// Entries are kept in memory for `ttl_secs`; with `persist` on they are also
+// written to disk, encrypted with a key bound to this machine and license, so
+// a restart doesn't cost the round-trip either.
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_machine_uid::MachineUidExt;
+
+use crate::api::{self, ApiResponseConfig};
+use crate::config_store::{self, JsonConfig, JsonConfigStore};
+
+const NONCE_LEN: usize = 12;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiConfigCacheSettings {
+    pub enabled: bool,
+    pub ttl_secs: u64,
+    // Keep an encrypted copy on disk across restarts
+    pub persist: bool,
+}
+
+impl Default for ApiConfigCacheSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ttl_secs: 900, // Model configs change rarely; tokens outlive this
+            persist: false,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct CachedConfig {
+    config: ApiResponseConfig,
+    fetched_at_ms: u64,
+}
+
+impl JsonConfig for ApiConfigCacheSettings {
+    const FILE_NAME: &'static str = "api_config_cache.json";
+    const LABEL: &'static str = "API config cache";
+}
+
+#[derive(Default)]
+pub struct ApiConfigCacheState {
+    settings: JsonConfigStore<ApiConfigCacheSettings>,
+    // Keyed by "provider/model"; None until the disk copy has been read
+    entries: Mutex<Option<HashMap<String, CachedConfig>>>,
+}
+
+fn get_cache_path(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(config_store::app_data_dir(app)?.join("api_config_cache.bin"))
+}
+
+fn load_settings(app: &AppHandle) -> Result<ApiConfigCacheSettings, String> {
+    app.state::<ApiConfigCacheState>().settings.load(app)
+}
+
+fn settings_or_default(app: &AppHandle) -> ApiConfigCacheSettings {
+    app.state::<ApiConfigCacheState>().settings.load_or_default(app)
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn cache_key(provider: Option<&str>, model: Option<&str>) -> String {
+    format!("{}/{}", provider.unwrap_or_default(), model.unwrap_or_default())
+}
+
+// Another machine or license can't decrypt the file, and a new license
+// simply reads as a cache miss
+async fn encryption_key(app: &AppHandle) -> Result<[u8; 32], String> {
+    let (license_key, instance_id, _) = api::get_stored_credentials(app).await?;
+    let machine_id = app
+        .machine_uid()
+        .get_machine_uid()
+        .ok()
+        .and_then(|uid| uid.id)
+        .ok_or_else(|| "Failed to read machine id".to_string())?;
+
+    let mut hasher = Sha256::new();
+    for part in ["pluely-api-config-cache", &machine_id, &license_key, &instance_id] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    Ok(hasher.finalize().into())
+}
+
+fn encrypt(key: &[u8; 32], plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let cipher = Aes256Gcm::new(key.into());
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext)
+        .map_err(|_| "Failed to encrypt API config cache".to_string())?;
+    Ok([nonce.as_slice(), &ciphertext].concat())
+}
+
+fn decrypt(key: &[u8; 32], data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() <= NONCE_LEN {
+        return Err("API config cache file is truncated".to_string());
+    }
+    let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+    Aes256Gcm::new(key.into())
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "Failed to decrypt API config cache".to_string())
+}
+
+async fn read_disk_cache(app: &AppHandle) -> Result<HashMap<String, CachedConfig>, String> {
+    let path = get_cache_path(app)?;
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+
+    let data = fs::read(&path).map_err(|e| format!("Failed to read API config cache: {}", e))?;
+    let plaintext = decrypt(&encryption_key(app).await?, &data)?;
+    serde_json::from_slice(&plaintext).map_err(|e| format!("Failed to parse API config cache: {}", e))
+}
+
+async fn write_disk_cache(app: &AppHandle, entries: &HashMap<String, CachedConfig>) -> Result<(), String> {
+    let plaintext = serde_json::to_vec(entries)
+        .map_err(|e| format!("Failed to serialize API config cache: {}", e))?;
+    let data = encrypt(&encryption_key(app).await?, &plaintext)?;
+    fs::write(get_cache_path(app)?, data).map_err(|e| format!("Failed to write API config cache: {}", e))
+}
+
+fn remove_disk_cache(app: &AppHandle) {
+    if let Ok(path) = get_cache_path(app) {
+        if path.exists() {
+            if let Err(e) = fs::remove_file(&path) {
+                tracing::warn!("Failed to remove API config cache: {}", e);
+            }
+        }
+    }
+}
+
+// Reads the disk copy into memory the first time the cache is used
+async fn ensure_loaded(app: &AppHandle, settings: &ApiConfigCacheSettings) {
+    let state = app.state::<ApiConfigCacheState>();
+    if state.entries.lock().map(|entries| entries.is_some()).unwrap_or(true) {
+        return;
+    }
+
+    let loaded = if settings.persist {
+        read_disk_cache(app).await.unwrap_or_else(|e| {
+            tracing::warn!("Ignoring API config cache on disk: {}", e);
+            HashMap::new()
+        })
+    } else {
+        HashMap::new()
+    };
+    let mut entries = match state.entries.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    entries.get_or_insert(loaded);
+}
+
+/// The cached configuration for this provider/model, if it's still fresh.
+pub async fn get(app: &AppHandle, provider: Option<&str>, model: Option<&str>) -> Option<ApiResponseConfig> {
+    let settings = settings_or_default(app);
+    if !settings.enabled {
+        return None;
+    }
+    ensure_loaded(app, &settings).await;
+
+    let state = app.state::<ApiConfigCacheState>();
+    let entries = state.entries.lock().ok()?;
+    let entry = entries.as_ref()?.get(&cache_key(provider, model))?;
+    let age_ms = now_ms().saturating_sub(entry.fetched_at_ms);
+    (age_ms < settings.ttl_secs * 1000).then(|| entry.config.clone())
+}
+
+pub async fn store(app: &AppHandle, provider: Option<&str>, model: Option<&str>, config: &ApiResponseConfig) {
+    let settings = settings_or_default(app);
+    if !settings.enabled {
+        return;
+    }
+    ensure_loaded(app, &settings).await;
+
+    let snapshot = {
+        let state = app.state::<ApiConfigCacheState>();
+        let Ok(mut entries) = state.entries.lock() else {
+            return;
+        };
+        let now = now_ms();
+        let entries = entries.get_or_insert_with(HashMap::new);
+        entries.retain(|_, entry| now.saturating_sub(entry.fetched_at_ms) < settings.ttl_secs * 1000);
+        entries.insert(
+            cache_key(provider, model),
+            CachedConfig {
+                config: config.clone(),
+                fetched_at_ms: now,
+            },
+        );
+        entries.clone()
+    };
+
+    if settings.persist {
+        if let Err(e) = write_disk_cache(app, &snapshot).await {
+            tracing::warn!("Failed to persist API config cache: {}", e);
+        }
+    }
+}
+
+/// Drop one provider/model, e.g. after its token was rejected. The disk copy
+/// is removed too and rewritten on the next store.
+pub fn invalidate(app: &AppHandle, provider: Option<&str>, model: Option<&str>) {
+    let state = app.state::<ApiConfigCacheState>();
+    if let Ok(mut entries) = state.entries.lock() {
+        if let Some(entries) = entries.as_mut() {
+            entries.remove(&cache_key(provider, model));
+        }
+    }
+    remove_disk_cache(app);
+}
+
+/// Drop every entry, e.g. when the license or selected model changes.
+pub fn clear(app: &AppHandle) {
+    let state = app.state::<ApiConfigCacheState>();
+    if let Ok(mut entries) = state.entries.lock() {
+        // Nothing left to read from disk either
+        *entries = Some(HashMap::new());
+    }
+    remove_disk_cache(app);
+}
+
+#[tauri::command]
+pub fn get_api_config_cache_settings(app: AppHandle) -> Result<ApiConfigCacheSettings, String> {
+    load_settings(&app)
+}
+
+#[tauri::command]
+pub fn update_api_config_cache_settings(
+    app: AppHandle,
+    settings: ApiConfigCacheSettings,
+) -> Result<(), String> {
+    if !(10..=86_400).contains(&settings.ttl_secs) {
+        return Err("Invalid ttl_secs: must be between 10 and 86400".to_string());
+    }
+
+    let (enabled, persist) = (settings.enabled, settings.persist);
+    app.state::<ApiConfigCacheState>().settings.save(&app, settings)?;
+
+    if !enabled {
+        clear(&app);
+    } else if !persist {
+        remove_disk_cache(&app);
+    }
+    Ok(())
+}
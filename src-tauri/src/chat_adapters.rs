@@ -0,0 +1,1499 @@
+// Provider adapters for chat streaming. `chat_stream_response` builds a
+// provider-neutral ChatInput, and the adapter picked by the model config's
+// `provider_type` turns it into the provider's request shape and maps its
+// SSE events back into content deltas, tool calls and usage.
+use crate::image_pipeline;
+use crate::sse::SseEvent;
+use reqwest::RequestBuilder;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+// Anthropic requires max_tokens; the model config body can override it
+const ANTHROPIC_DEFAULT_MAX_TOKENS: u64 = 4096;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatProviderType {
+    // Any OpenAI-compatible chat completions endpoint
+    #[default]
+    OpenAi,
+    Anthropic,
+    Gemini,
+}
+
+// How hard a reasoning model should think. OpenAI takes the level as is;
+// Anthropic and Gemini take a token budget instead.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    Minimal,
+    Low,
+    Medium,
+    High,
+}
+
+impl ReasoningEffort {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ReasoningEffort::Minimal => "minimal",
+            ReasoningEffort::Low => "low",
+            ReasoningEffort::Medium => "medium",
+            ReasoningEffort::High => "high",
+        }
+    }
+
+    fn budget_tokens(&self) -> u64 {
+        match self {
+            ReasoningEffort::Minimal => 1024, // Anthropic's minimum
+            ReasoningEffort::Low => 2048,
+            ReasoningEffort::Medium => 8192,
+            ReasoningEffort::High => 24576,
+        }
+    }
+}
+
+// A JSON schema the answer must match. OpenAI enforces it natively; the
+// other providers are instructed through the system prompt, and the result
+// is validated in Rust either way (see structured_output).
+#[derive(Debug, Clone, Deserialize)]
+pub struct ResponseFormat {
+    // OpenAI requires a name: letters, digits, underscores and dashes
+    #[serde(default = "default_response_format_name")]
+    pub name: String,
+    pub schema: Value,
+}
+
+fn default_response_format_name() -> String {
+    "response".to_string()
+}
+
+impl ResponseFormat {
+    pub fn instructions(&self) -> String {
+        format!(
+            "Respond with a single JSON value that matches this JSON schema, without markdown fences or any other text:\n{}",
+            self.schema
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ChatImage {
+    pub mime: String,
+    pub data: String,
+}
+
+impl ChatImage {
+    // Labels the image by its magic bytes; image_pipeline::prepare_images
+    // also downscales and re-encodes
+    pub fn from_base64(data: &str) -> Self {
+        Self {
+            mime: image_pipeline::detect_mime(data).to_string(),
+            data: data.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ChatInput {
+    pub system_prompt: Option<String>,
+    // OpenAI-format messages as sent by the frontend
+    pub history: Vec<Value>,
+    pub user_message: String,
+    pub images: Vec<ChatImage>,
+    // Tool definitions in the OpenAI function format
+    pub tools: Vec<Value>,
+    pub reasoning_effort: Option<ReasoningEffort>,
+    pub response_format: Option<ResponseFormat>,
+    // History index where this request's own messages (the committed user
+    // turn and tool rounds) begin; context trimming never drops those
+    pub turn_start: Option<usize>,
+}
+
+impl ChatInput {
+    /// Move the pending user message into the history so follow-up turns
+    /// (e.g. after tool results) don't repeat it.
+    pub fn commit_user_turn(&mut self) {
+        if self.user_message.is_empty() && self.images.is_empty() {
+            return;
+        }
+
+        let images = std::mem::take(&mut self.images);
+        let text = std::mem::take(&mut self.user_message);
+        self.turn_start = Some(self.history.len());
+        self.history.push(openai_user_message(&text, &images));
+    }
+
+    pub fn push_assistant_tool_calls(&mut self, text: &str, tool_calls: &ToolCalls) {
+        let calls: Vec<Value> = tool_calls
+            .calls
+            .iter()
+            .map(|call| {
+                let mut value = json!({
+                    "id": call.id,
+                    "type": "function",
+                    "function": {
+                        "name": call.name,
+                        "arguments": call.arguments
+                    }
+                });
+                if let Some(signature) = call.thought_signature.as_ref() {
+                    value[THOUGHT_SIGNATURE_KEY] = json!(signature);
+                }
+                value
+            })
+            .collect();
+
+        let mut message = json!({
+            "role": "assistant",
+            "content": if text.is_empty() { Value::Null } else { json!(text) },
+            "tool_calls": calls
+        });
+        if !tool_calls.thinking.is_empty() {
+            message[THINKING_BLOCKS_KEY] = json!(tool_calls.thinking);
+        }
+        self.history.push(message);
+    }
+
+    /// Record a rejected answer and the user's request to fix it.
+    pub fn push_retry_feedback(&mut self, answer: &str, feedback: &str) {
+        self.history.push(json!({
+            "role": "assistant",
+            "content": answer
+        }));
+        self.history.push(json!({
+            "role": "user",
+            "content": feedback
+        }));
+    }
+
+    pub fn push_tool_result(&mut self, call: &ToolCall, content: String) {
+        self.history.push(json!({
+            "role": "tool",
+            "tool_call_id": call.id,
+            "content": content
+        }));
+    }
+
+    // Tool messages can't carry images on every provider, so those go in a
+    // user message right after the tool results
+    pub fn push_user_images(&mut self, text: &str, images: &[ChatImage]) {
+        self.history.push(openai_user_message(text, images));
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    // Raw JSON arguments as produced by the model
+    pub arguments: String,
+    // Gemini's opaque signature on the functionCall part; it has to come
+    // back with the call or the follow-up request is rejected
+    #[serde(skip)]
+    pub thought_signature: Option<String>,
+}
+
+// The tool calls of one assistant turn
+#[derive(Debug, Default)]
+pub struct ToolCalls {
+    pub calls: Vec<ToolCall>,
+    // Anthropic thinking blocks (with their signatures) that preceded the
+    // calls. With extended thinking on, they must open the assistant message
+    // when the tool results are sent back.
+    pub thinking: Vec<Value>,
+}
+
+// Provider-specific fields kept on the OpenAI-shaped history; only the
+// matching adapter reads them and openai_body drops them
+const THINKING_BLOCKS_KEY: &str = "thinking_blocks";
+const THOUGHT_SIGNATURE_KEY: &str = "thought_signature";
+
+// One streamed fragment of a tool call. OpenAI and Anthropic send the name
+// first and the arguments in pieces; Gemini sends whole calls (index None).
+#[derive(Debug, Default)]
+pub struct ToolCallDelta {
+    pub index: Option<usize>,
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub arguments: String,
+    pub thought_signature: Option<String>,
+}
+
+// One streamed fragment of an Anthropic thinking block, keyed by block index
+#[derive(Debug)]
+pub enum ThinkingDelta {
+    // content_block_start: the block as sent (thinking or redacted_thinking)
+    Start(usize, Value),
+    Thinking(usize, String),
+    Signature(usize, String),
+}
+
+#[derive(Debug, Default)]
+pub struct ToolCallAccumulator {
+    calls: BTreeMap<usize, ToolCall>,
+    thinking: BTreeMap<usize, Value>,
+}
+
+impl ToolCallAccumulator {
+    pub fn push(&mut self, delta: ToolCallDelta) {
+        let index = delta
+            .index
+            .unwrap_or_else(|| self.calls.keys().next_back().map_or(0, |last| last + 1));
+        let call = self.calls.entry(index).or_insert_with(|| ToolCall {
+            id: String::new(),
+            name: String::new(),
+            arguments: String::new(),
+            thought_signature: None,
+        });
+
+        if let Some(id) = delta.id.filter(|id| !id.is_empty()) {
+            call.id = id;
+        }
+        if let Some(name) = delta.name.filter(|name| !name.is_empty()) {
+            call.name = name;
+        }
+        if let Some(signature) = delta.thought_signature {
+            call.thought_signature = Some(signature);
+        }
+        call.arguments.push_str(&delta.arguments);
+    }
+
+    pub fn push_thinking(&mut self, delta: ThinkingDelta) {
+        let (index, field, text) = match delta {
+            ThinkingDelta::Start(index, block) => {
+                self.thinking.insert(index, block);
+                return;
+            }
+            ThinkingDelta::Thinking(index, text) => (index, "thinking", text),
+            ThinkingDelta::Signature(index, signature) => (index, "signature", signature),
+        };
+        let Some(block) = self.thinking.get_mut(&index) else {
+            return;
+        };
+        let current = block.get(field).and_then(|v| v.as_str()).unwrap_or_default();
+        block[field] = json!(format!("{}{}", current, text));
+    }
+
+    pub fn finish(self) -> ToolCalls {
+        let calls = self
+            .calls
+            .into_iter()
+            .filter(|(_, call)| !call.name.is_empty())
+            .map(|(index, mut call)| {
+                // Gemini doesn't always send ids; results are matched back by id
+                if call.id.is_empty() {
+                    call.id = format!("call_{}", index);
+                }
+                if call.arguments.trim().is_empty() {
+                    call.arguments = "{}".to_string();
+                }
+                call
+            })
+            .collect();
+
+        ToolCalls {
+            calls,
+            thinking: self.thinking.into_values().collect(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct StreamUpdate {
+    pub content: Option<String>,
+    // Thinking/reasoning text, kept apart from the answer
+    pub reasoning: Option<String>,
+    pub tool_calls: Vec<ToolCallDelta>,
+    pub thinking: Vec<ThinkingDelta>,
+    // OpenAI-shaped usage ({prompt_tokens, completion_tokens, total_tokens}),
+    // possibly partial; merge successive updates with `merge_usage`
+    pub usage: Option<Value>,
+    pub done: bool,
+    pub error: Option<String>,
+}
+
+impl ChatProviderType {
+    /// Gemini puts the model and method in the path; the configured url may be
+    /// the full streaming url or just the API base.
+    pub fn stream_url(&self, url: &str, model: &str) -> String {
+        match self {
+            ChatProviderType::Gemini if !url.contains(":streamGenerateContent") => format!(
+                "{}/models/{}:streamGenerateContent?alt=sse",
+                url.trim_end_matches('/'),
+                model
+            ),
+            // Without alt=sse Gemini streams one big JSON array instead of events
+            ChatProviderType::Gemini if !url.contains("alt=sse") => {
+                let separator = if url.contains('?') { '&' } else { '?' };
+                format!("{}{}alt=sse", url, separator)
+            }
+            _ => url.to_string(),
+        }
+    }
+
+    pub fn build_body(&self, model: &str, input: &ChatInput) -> Value {
+        match self {
+            ChatProviderType::OpenAi => openai_body(model, input),
+            ChatProviderType::Anthropic => anthropic_body(model, input),
+            ChatProviderType::Gemini => gemini_body(input),
+        }
+    }
+
+    pub fn authorize(&self, request: RequestBuilder, token: &str) -> RequestBuilder {
+        match self {
+            ChatProviderType::OpenAi => {
+                request.header("Authorization", format!("Bearer {}", token))
+            }
+            ChatProviderType::Anthropic => request
+                .header("x-api-key", token)
+                .header("anthropic-version", ANTHROPIC_VERSION),
+            ChatProviderType::Gemini => request.header("x-goog-api-key", token),
+        }
+    }
+
+    pub fn parse_event(&self, event: &SseEvent) -> StreamUpdate {
+        match self {
+            ChatProviderType::OpenAi => parse_openai_event(event),
+            ChatProviderType::Anthropic => parse_anthropic_event(event),
+            ChatProviderType::Gemini => parse_gemini_event(event),
+        }
+    }
+}
+
+/// Shallow-merge `update` into `usage`, filling in total_tokens once both
+/// halves are known (Anthropic reports input and output in separate events).
+pub fn merge_usage(usage: &mut Option<Value>, update: Value) {
+    let Some(update_obj) = update.as_object() else {
+        return;
+    };
+
+    let merged = usage.get_or_insert_with(|| json!({}));
+    if let Some(merged_obj) = merged.as_object_mut() {
+        for (key, value) in update_obj {
+            merged_obj.insert(key.clone(), value.clone());
+        }
+
+        let prompt = merged_obj.get("prompt_tokens").and_then(|v| v.as_u64());
+        let completion = merged_obj.get("completion_tokens").and_then(|v| v.as_u64());
+        if let (Some(prompt), Some(completion), false) =
+            (prompt, completion, update_obj.contains_key("total_tokens"))
+        {
+            merged_obj.insert("total_tokens".to_string(), json!(prompt + completion));
+        }
+    }
+}
+
+fn openai_body(model: &str, input: &ChatInput) -> Value {
+    let mut messages: Vec<Value> = Vec::new();
+
+    if let Some(system_prompt) = input.system_prompt.as_ref() {
+        messages.push(json!({
+            "role": "system",
+            "content": system_prompt
+        }));
+    }
+
+    messages.extend(input.history.iter().map(openai_history_message));
+    if has_user_turn(input) {
+        messages.push(openai_user_message(&input.user_message, &input.images));
+    }
+
+    let mut body = json!({
+        "model": model,
+        "messages": messages,
+        "stream": true
+    });
+    if !input.tools.is_empty() {
+        body["tools"] = json!(input.tools);
+    }
+    if let Some(effort) = input.reasoning_effort {
+        body["reasoning_effort"] = json!(effort.as_str());
+    }
+    if let Some(format) = input.response_format.as_ref() {
+        body["response_format"] = json!({
+            "type": "json_schema",
+            "json_schema": {
+                "name": format.name,
+                "schema": format.schema
+            }
+        });
+    }
+    body
+}
+
+fn openai_user_message(text: &str, images: &[ChatImage]) -> Value {
+    let mut content = vec![json!({
+        "type": "text",
+        "text": text
+    })];
+    for image in images {
+        content.push(json!({
+            "type": "image_url",
+            "image_url": {
+                "url": format!("data:{};base64,{}", image.mime, image.data)
+            }
+        }));
+    }
+
+    json!({
+        "role": "user",
+        "content": content
+    })
+}
+
+// False on follow-up turns, where the user message already sits in the history
+fn has_user_turn(input: &ChatInput) -> bool {
+    !input.user_message.is_empty() || !input.images.is_empty()
+}
+
+fn parse_openai_event(event: &SseEvent) -> StreamUpdate {
+    let mut update = StreamUpdate::default();
+    if event.data == "[DONE]" {
+        update.done = true;
+        return update;
+    }
+
+    let Ok(parsed) = serde_json::from_str::<Value>(&event.data) else {
+        return update;
+    };
+
+    if let Some(message) = parsed
+        .get("error")
+        .and_then(|e| e.get("message").or(Some(e)))
+        .and_then(|m| m.as_str())
+    {
+        update.error = Some(message.to_string());
+        return update;
+    }
+
+    update.usage = parsed.get("usage").filter(|usage| !usage.is_null()).cloned();
+    let Some(delta) = parsed
+        .get("choices")
+        .and_then(|c| c.as_array())
+        .and_then(|choices| choices.first())
+        .and_then(|choice| choice.get("delta"))
+    else {
+        return update;
+    };
+
+    update.content = delta
+        .get("content")
+        .and_then(|c| c.as_str())
+        .map(|content| content.to_string());
+    // DeepSeek/vLLM use reasoning_content, OpenRouter and Ollama use reasoning
+    update.reasoning = delta
+        .get("reasoning_content")
+        .or_else(|| delta.get("reasoning"))
+        .and_then(|r| r.as_str())
+        .filter(|reasoning| !reasoning.is_empty())
+        .map(|reasoning| reasoning.to_string());
+    if let Some(tool_calls) = delta.get("tool_calls").and_then(|t| t.as_array()) {
+        update.tool_calls = tool_calls
+            .iter()
+            .map(|call| ToolCallDelta {
+                index: call.get("index").and_then(|i| i.as_u64()).map(|i| i as usize),
+                id: call.get("id").and_then(|i| i.as_str()).map(|id| id.to_string()),
+                name: call
+                    .pointer("/function/name")
+                    .and_then(|n| n.as_str())
+                    .map(|name| name.to_string()),
+                arguments: call
+                    .pointer("/function/arguments")
+                    .and_then(|a| a.as_str())
+                    .unwrap_or_default()
+                    .to_string(),
+                thought_signature: None,
+            })
+            .collect();
+    }
+    update
+}
+
+// History message without the fields other providers' adapters keep on it;
+// strict OpenAI-compatible servers reject unknown keys
+fn openai_history_message(message: &Value) -> Value {
+    let mut message = message.clone();
+    if let Some(object) = message.as_object_mut() {
+        object.remove(THINKING_BLOCKS_KEY);
+    }
+    if let Some(calls) = message.get_mut("tool_calls").and_then(|c| c.as_array_mut()) {
+        for call in calls.iter_mut().filter_map(|call| call.as_object_mut()) {
+            call.remove(THOUGHT_SIGNATURE_KEY);
+        }
+    }
+    message
+}
+
+fn anthropic_body(model: &str, input: &ChatInput) -> Value {
+    // System messages in the history are folded into the top-level system field
+    let mut system_parts: Vec<String> = input.system_prompt.iter().cloned().collect();
+    system_parts.extend(input.response_format.as_ref().map(ResponseFormat::instructions));
+    let mut messages: Vec<Value> = Vec::new();
+
+    for message in &input.history {
+        let role = message.get("role").and_then(|r| r.as_str()).unwrap_or("user");
+        let content = message.get("content").cloned().unwrap_or(Value::Null);
+
+        match role {
+            "system" => system_parts.push(openai_content_text(&content)),
+            "tool" => {
+                let result = json!({
+                    "type": "tool_result",
+                    "tool_use_id": message.get("tool_call_id").and_then(|i| i.as_str()).unwrap_or_default(),
+                    "content": openai_content_text(&content)
+                });
+                push_anthropic_message(&mut messages, "user", vec![result]);
+            }
+            "assistant" => {
+                let mut blocks: Vec<Value> = message
+                    .get(THINKING_BLOCKS_KEY)
+                    .and_then(|t| t.as_array())
+                    .cloned()
+                    .unwrap_or_default();
+                blocks.extend(anthropic_content(&content));
+                for (id, name, arguments, _) in openai_tool_calls(message) {
+                    blocks.push(json!({
+                        "type": "tool_use",
+                        "id": id,
+                        "name": name,
+                        "input": arguments
+                    }));
+                }
+                push_anthropic_message(&mut messages, "assistant", blocks);
+            }
+            _ => push_anthropic_message(&mut messages, "user", anthropic_content(&content)),
+        }
+    }
+
+    if has_user_turn(input) {
+        let mut user_content = Vec::new();
+        for image in &input.images {
+            user_content.push(anthropic_image(&image.mime, &image.data));
+        }
+        user_content.push(json!({
+            "type": "text",
+            "text": input.user_message
+        }));
+        push_anthropic_message(&mut messages, "user", user_content);
+    }
+
+    let mut body = json!({
+        "model": model,
+        "messages": messages,
+        "max_tokens": ANTHROPIC_DEFAULT_MAX_TOKENS,
+        "stream": true
+    });
+    if !input.tools.is_empty() {
+        let tools: Vec<Value> = input.tools.iter().filter_map(anthropic_tool).collect();
+        body["tools"] = json!(tools);
+    }
+    if let Some(effort) = input.reasoning_effort {
+        // max_tokens includes the thinking budget, so leave room for the answer
+        let budget = effort.budget_tokens();
+        body["thinking"] = json!({ "type": "enabled", "budget_tokens": budget });
+        body["max_tokens"] = json!(budget + ANTHROPIC_DEFAULT_MAX_TOKENS);
+    }
+    let system = system_parts
+        .into_iter()
+        .filter(|part| !part.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    if !system.is_empty() {
+        body["system"] = json!(system);
+    }
+    body
+}
+
+// Anthropic wants alternating roles, so consecutive messages from the same
+// role (tool results plus the screenshot that follows them) share one turn
+fn push_anthropic_message(messages: &mut Vec<Value>, role: &str, blocks: Vec<Value>) {
+    if blocks.is_empty() {
+        return;
+    }
+
+    if let Some(last) = messages.last_mut().filter(|last| last["role"] == role) {
+        if let Some(content) = last["content"].as_array_mut() {
+            content.extend(blocks);
+            return;
+        }
+    }
+    messages.push(json!({
+        "role": role,
+        "content": blocks
+    }));
+}
+
+// OpenAI function tool -> Anthropic tool
+fn anthropic_tool(tool: &Value) -> Option<Value> {
+    let function = tool.get("function")?;
+    let mut converted = json!({
+        "name": function.get("name")?,
+        "input_schema": function
+            .get("parameters")
+            .cloned()
+            .unwrap_or_else(|| json!({ "type": "object", "properties": {} }))
+    });
+    if let Some(description) = function.get("description") {
+        converted["description"] = description.clone();
+    }
+    Some(converted)
+}
+
+// OpenAI content (string or parts array) -> Anthropic content blocks.
+// Empty text blocks are rejected by the API, so they are dropped.
+fn anthropic_content(content: &Value) -> Vec<Value> {
+    let Some(parts) = content.as_array() else {
+        let text = openai_content_text(content);
+        if text.is_empty() {
+            return Vec::new();
+        }
+        return vec![json!({ "type": "text", "text": text })];
+    };
+
+    parts
+        .iter()
+        .filter_map(|part| match part.get("type").and_then(|t| t.as_str()) {
+            Some("text") => part
+                .get("text")
+                .and_then(|t| t.as_str())
+                .filter(|text| !text.is_empty())
+                .map(|text| json!({ "type": "text", "text": text })),
+            Some("image_url") => part
+                .get("image_url")
+                .and_then(|image| image.get("url"))
+                .and_then(|url| url.as_str())
+                .and_then(parse_data_url)
+                .map(|(mime, data)| anthropic_image(&mime, &data)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn anthropic_image(mime: &str, data: &str) -> Value {
+    json!({
+        "type": "image",
+        "source": {
+            "type": "base64",
+            "media_type": mime,
+            "data": data
+        }
+    })
+}
+
+fn parse_anthropic_event(event: &SseEvent) -> StreamUpdate {
+    let mut update = StreamUpdate::default();
+    let Ok(parsed) = serde_json::from_str::<Value>(&event.data) else {
+        return update;
+    };
+
+    // The `type` field mirrors the SSE event name; prefer it in case a proxy drops `event:`
+    let kind = parsed
+        .get("type")
+        .and_then(|t| t.as_str())
+        .unwrap_or(event.event.as_str());
+
+    match kind {
+        "message_start" => {
+            let input_tokens = parsed
+                .pointer("/message/usage/input_tokens")
+                .and_then(|v| v.as_u64());
+            if let Some(input_tokens) = input_tokens {
+                update.usage = Some(json!({ "prompt_tokens": input_tokens }));
+            }
+        }
+        "content_block_start" => {
+            let block = &parsed["content_block"];
+            match block.get("type").and_then(|t| t.as_str()) {
+                Some("tool_use") => update.tool_calls.push(ToolCallDelta {
+                    index: block_index(&parsed),
+                    id: block.get("id").and_then(|i| i.as_str()).map(|id| id.to_string()),
+                    name: block.get("name").and_then(|n| n.as_str()).map(|name| name.to_string()),
+                    ..Default::default()
+                }),
+                Some("thinking") | Some("redacted_thinking") => {
+                    if let Some(index) = block_index(&parsed) {
+                        update.thinking.push(ThinkingDelta::Start(index, block.clone()));
+                    }
+                }
+                _ => {}
+            }
+        }
+        "content_block_delta" => match parsed.pointer("/delta/type").and_then(|t| t.as_str()) {
+            Some("text_delta") => {
+                update.content = parsed
+                    .pointer("/delta/text")
+                    .and_then(|t| t.as_str())
+                    .map(|text| text.to_string());
+            }
+            Some("thinking_delta") => {
+                update.reasoning = parsed
+                    .pointer("/delta/thinking")
+                    .and_then(|t| t.as_str())
+                    .map(|thinking| thinking.to_string());
+                if let (Some(index), Some(thinking)) = (block_index(&parsed), &update.reasoning) {
+                    update.thinking.push(ThinkingDelta::Thinking(index, thinking.clone()));
+                }
+            }
+            Some("signature_delta") => {
+                let signature = parsed.pointer("/delta/signature").and_then(|s| s.as_str());
+                if let (Some(index), Some(signature)) = (block_index(&parsed), signature) {
+                    update.thinking.push(ThinkingDelta::Signature(index, signature.to_string()));
+                }
+            }
+            Some("input_json_delta") => {
+                update.tool_calls.push(ToolCallDelta {
+                    index: block_index(&parsed),
+                    arguments: parsed
+                        .pointer("/delta/partial_json")
+                        .and_then(|j| j.as_str())
+                        .unwrap_or_default()
+                        .to_string(),
+                    ..Default::default()
+                });
+            }
+            _ => {}
+        },
+        "message_delta" => {
+            let output_tokens = parsed
+                .pointer("/usage/output_tokens")
+                .and_then(|v| v.as_u64());
+            if let Some(output_tokens) = output_tokens {
+                update.usage = Some(json!({ "completion_tokens": output_tokens }));
+            }
+        }
+        "message_stop" => update.done = true,
+        "error" => {
+            update.error = Some(
+                parsed
+                    .pointer("/error/message")
+                    .and_then(|m| m.as_str())
+                    .unwrap_or("Anthropic stream error")
+                    .to_string(),
+            );
+        }
+        _ => {}
+    }
+
+    update
+}
+
+fn block_index(parsed: &Value) -> Option<usize> {
+    parsed.get("index").and_then(|i| i.as_u64()).map(|i| i as usize)
+}
+
+fn gemini_body(input: &ChatInput) -> Value {
+    let mut system_parts: Vec<String> = input.system_prompt.iter().cloned().collect();
+    system_parts.extend(input.response_format.as_ref().map(ResponseFormat::instructions));
+    let mut contents: Vec<Value> = Vec::new();
+    // functionResponse needs the function name, OpenAI tool messages only carry the id
+    let mut tool_names: HashMap<String, String> = HashMap::new();
+
+    for message in &input.history {
+        let role = message.get("role").and_then(|r| r.as_str()).unwrap_or("user");
+        let content = message.get("content").cloned().unwrap_or(Value::Null);
+
+        match role {
+            "system" => system_parts.push(openai_content_text(&content)),
+            "tool" => {
+                let id = message.get("tool_call_id").and_then(|i| i.as_str()).unwrap_or_default();
+                let response = json!({
+                    "functionResponse": {
+                        "name": tool_names.get(id).cloned().unwrap_or_default(),
+                        "response": { "content": openai_content_text(&content) }
+                    }
+                });
+                push_gemini_content(&mut contents, "user", vec![response]);
+            }
+            "assistant" => {
+                let mut parts = gemini_parts(&content);
+                for (id, name, arguments, signature) in openai_tool_calls(message) {
+                    let mut part = json!({
+                        "functionCall": {
+                            "name": name,
+                            "args": arguments
+                        }
+                    });
+                    if let Some(signature) = signature {
+                        part["thoughtSignature"] = json!(signature);
+                    }
+                    parts.push(part);
+                    tool_names.insert(id, name);
+                }
+                push_gemini_content(&mut contents, "model", parts);
+            }
+            _ => push_gemini_content(&mut contents, "user", gemini_parts(&content)),
+        }
+    }
+
+    if has_user_turn(input) {
+        let mut user_parts = vec![json!({ "text": input.user_message })];
+        for image in &input.images {
+            user_parts.push(gemini_image(&image.mime, &image.data));
+        }
+        push_gemini_content(&mut contents, "user", user_parts);
+    }
+
+    let mut body = json!({ "contents": contents });
+    if !input.tools.is_empty() {
+        let declarations: Vec<Value> = input
+            .tools
+            .iter()
+            .filter_map(|tool| tool.get("function").cloned())
+            .collect();
+        body["tools"] = json!([{ "functionDeclarations": declarations }]);
+    }
+    if let Some(effort) = input.reasoning_effort {
+        // Thought summaries are only streamed when asked for
+        body["generationConfig"]["thinkingConfig"] = json!({
+            "thinkingBudget": effort.budget_tokens(),
+            "includeThoughts": true
+        });
+    }
+    // JSON mode can't be combined with function calling
+    if input.response_format.is_some() && input.tools.is_empty() {
+        body["generationConfig"]["responseMimeType"] = json!("application/json");
+    }
+    let system = system_parts
+        .into_iter()
+        .filter(|part| !part.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    if !system.is_empty() {
+        body["system_instruction"] = json!({ "parts": [{ "text": system }] });
+    }
+    body
+}
+
+fn push_gemini_content(contents: &mut Vec<Value>, role: &str, parts: Vec<Value>) {
+    if parts.is_empty() {
+        return;
+    }
+
+    if let Some(last) = contents.last_mut().filter(|last| last["role"] == role) {
+        if let Some(existing) = last["parts"].as_array_mut() {
+            existing.extend(parts);
+            return;
+        }
+    }
+    contents.push(json!({
+        "role": role,
+        "parts": parts
+    }));
+}
+
+// OpenAI content (string or parts array) -> Gemini parts
+fn gemini_parts(content: &Value) -> Vec<Value> {
+    let Some(parts) = content.as_array() else {
+        let text = openai_content_text(content);
+        if text.is_empty() {
+            return Vec::new();
+        }
+        return vec![json!({ "text": text })];
+    };
+
+    parts
+        .iter()
+        .filter_map(|part| match part.get("type").and_then(|t| t.as_str()) {
+            Some("text") => part
+                .get("text")
+                .and_then(|t| t.as_str())
+                .filter(|text| !text.is_empty())
+                .map(|text| json!({ "text": text })),
+            Some("image_url") => part
+                .get("image_url")
+                .and_then(|image| image.get("url"))
+                .and_then(|url| url.as_str())
+                .and_then(parse_data_url)
+                .map(|(mime, data)| gemini_image(&mime, &data)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn gemini_image(mime: &str, data: &str) -> Value {
+    json!({
+        "inline_data": {
+            "mime_type": mime,
+            "data": data
+        }
+    })
+}
+
+// Gemini has no end-of-stream sentinel; the stream simply closes
+fn parse_gemini_event(event: &SseEvent) -> StreamUpdate {
+    let mut update = StreamUpdate::default();
+    let Ok(parsed) = serde_json::from_str::<Value>(&event.data) else {
+        return update;
+    };
+
+    if let Some(error) = parsed.get("error") {
+        update.error = Some(
+            error
+                .get("message")
+                .and_then(|m| m.as_str())
+                .unwrap_or("Gemini stream error")
+                .to_string(),
+        );
+        return update;
+    }
+
+    let parts = parsed
+        .pointer("/candidates/0/content/parts")
+        .and_then(|parts| parts.as_array())
+        .map(|parts| parts.as_slice())
+        .unwrap_or_default();
+    // Thought summaries are text parts flagged with `thought`
+    let (thoughts, answer): (Vec<&Value>, Vec<&Value>) = parts
+        .iter()
+        .partition(|part| part.get("thought").and_then(|t| t.as_bool()) == Some(true));
+    let text_of = |parts: Vec<&Value>| -> Option<String> {
+        let text: String = parts
+            .iter()
+            .filter_map(|part| part.get("text").and_then(|t| t.as_str()))
+            .collect();
+        Some(text).filter(|text| !text.is_empty())
+    };
+    update.content = text_of(answer);
+    update.reasoning = text_of(thoughts);
+    update.tool_calls = parts
+        .iter()
+        .filter_map(|part| Some((part.get("functionCall")?, part)))
+        .map(|(call, part)| ToolCallDelta {
+            index: None,
+            id: call.get("id").and_then(|i| i.as_str()).map(|id| id.to_string()),
+            name: call.get("name").and_then(|n| n.as_str()).map(|name| name.to_string()),
+            arguments: call.get("args").map(|args| args.to_string()).unwrap_or_default(),
+            thought_signature: part
+                .get("thoughtSignature")
+                .and_then(|s| s.as_str())
+                .map(|signature| signature.to_string()),
+        })
+        .collect();
+
+    if let Some(metadata) = parsed.get("usageMetadata") {
+        let count = |key: &str| metadata.get(key).and_then(|v| v.as_u64());
+        let mut usage = serde_json::Map::new();
+        if let Some(prompt) = count("promptTokenCount") {
+            usage.insert("prompt_tokens".to_string(), json!(prompt));
+        }
+        if let Some(completion) = count("candidatesTokenCount") {
+            usage.insert("completion_tokens".to_string(), json!(completion));
+        }
+        if let Some(total) = count("totalTokenCount") {
+            usage.insert("total_tokens".to_string(), json!(total));
+        }
+        if !usage.is_empty() {
+            update.usage = Some(Value::Object(usage));
+        }
+    }
+
+    update
+}
+
+/// Plain text of an OpenAI message content (string or parts array).
+pub fn openai_content_text(content: &Value) -> String {
+    match content {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| part.get("text").and_then(|t| t.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+// (id, name, parsed arguments, Gemini thought signature) for each tool call
+// on an OpenAI assistant message
+fn openai_tool_calls(message: &Value) -> Vec<(String, String, Value, Option<String>)> {
+    let Some(calls) = message.get("tool_calls").and_then(|c| c.as_array()) else {
+        return Vec::new();
+    };
+
+    calls
+        .iter()
+        .filter_map(|call| {
+            let name = call.pointer("/function/name").and_then(|n| n.as_str())?;
+            let arguments = call
+                .pointer("/function/arguments")
+                .and_then(|a| a.as_str())
+                .and_then(|a| serde_json::from_str(a).ok())
+                .unwrap_or_else(|| json!({}));
+            let id = call.get("id").and_then(|i| i.as_str()).unwrap_or_default();
+            let signature = call
+                .get(THOUGHT_SIGNATURE_KEY)
+                .and_then(|s| s.as_str())
+                .map(|signature| signature.to_string());
+            Some((id.to_string(), name.to_string(), arguments, signature))
+        })
+        .collect()
+}
+
+// "data:image/png;base64,AAAA" -> ("image/png", "AAAA")
+fn parse_data_url(url: &str) -> Option<(String, String)> {
+    let rest = url.strip_prefix("data:")?;
+    let (meta, data) = rest.split_once(',')?;
+    let mime = meta.strip_suffix(";base64")?;
+    Some((mime.to_string(), data.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(name: &str, data: Value) -> SseEvent {
+        SseEvent {
+            event: name.to_string(),
+            data: data.to_string(),
+            id: None,
+            retry: None,
+        }
+    }
+
+    fn weather_tool() -> Value {
+        json!({
+            "type": "function",
+            "function": {
+                "name": "get_weather",
+                "description": "Current weather for a city",
+                "parameters": {
+                    "type": "object",
+                    "properties": { "city": { "type": "string" } }
+                }
+            }
+        })
+    }
+
+    fn weather_call() -> ToolCall {
+        ToolCall {
+            id: "call_1".to_string(),
+            name: "get_weather".to_string(),
+            arguments: r#"{"city":"Oslo"}"#.to_string(),
+            thought_signature: None,
+        }
+    }
+
+    #[test]
+    fn anthropic_moves_system_prompts_to_top_level() {
+        let input = ChatInput {
+            system_prompt: Some("Be brief.".to_string()),
+            history: vec![
+                json!({ "role": "system", "content": "Answer in English." }),
+                json!({ "role": "user", "content": "Hi" }),
+                json!({ "role": "assistant", "content": "Hello!" }),
+            ],
+            user_message: "Weather?".to_string(),
+            ..Default::default()
+        };
+
+        let body = ChatProviderType::Anthropic.build_body("claude", &input);
+        assert_eq!(body["system"], "Be brief.\n\nAnswer in English.");
+        assert_eq!(body["model"], "claude");
+        assert_eq!(body["stream"], true);
+        assert_eq!(
+            body["messages"],
+            json!([
+                { "role": "user", "content": [{ "type": "text", "text": "Hi" }] },
+                { "role": "assistant", "content": [{ "type": "text", "text": "Hello!" }] },
+                { "role": "user", "content": [{ "type": "text", "text": "Weather?" }] },
+            ])
+        );
+    }
+
+    #[test]
+    fn anthropic_converts_images_to_base64_blocks() {
+        let input = ChatInput {
+            history: vec![json!({
+                "role": "user",
+                "content": [
+                    { "type": "text", "text": "Earlier" },
+                    { "type": "image_url", "image_url": { "url": "data:image/jpeg;base64,BBBB" } }
+                ]
+            })],
+            user_message: "What is this?".to_string(),
+            images: vec![ChatImage {
+                mime: "image/png".to_string(),
+                data: "AAAA".to_string(),
+            }],
+            ..Default::default()
+        };
+
+        let body = ChatProviderType::Anthropic.build_body("claude", &input);
+        let image = |mime: &str, data: &str| {
+            json!({
+                "type": "image",
+                "source": { "type": "base64", "media_type": mime, "data": data }
+            })
+        };
+        // Both user turns merge into one, since roles must alternate
+        assert_eq!(
+            body["messages"],
+            json!([{
+                "role": "user",
+                "content": [
+                    { "type": "text", "text": "Earlier" },
+                    image("image/jpeg", "BBBB"),
+                    image("image/png", "AAAA"),
+                    { "type": "text", "text": "What is this?" },
+                ]
+            }])
+        );
+    }
+
+    #[test]
+    fn anthropic_tool_use_round_trip() {
+        let mut input = ChatInput {
+            user_message: "Weather in Oslo?".to_string(),
+            tools: vec![weather_tool()],
+            ..Default::default()
+        };
+        let thinking = json!({ "type": "thinking", "thinking": "Look it up.", "signature": "sig" });
+        input.commit_user_turn();
+        input.push_assistant_tool_calls(
+            "Checking.",
+            &ToolCalls {
+                calls: vec![weather_call()],
+                thinking: vec![thinking.clone()],
+            },
+        );
+        input.push_tool_result(&weather_call(), "Sunny, 21C".to_string());
+
+        let body = ChatProviderType::Anthropic.build_body("claude", &input);
+        assert_eq!(
+            body["tools"],
+            json!([{
+                "name": "get_weather",
+                "description": "Current weather for a city",
+                "input_schema": {
+                    "type": "object",
+                    "properties": { "city": { "type": "string" } }
+                }
+            }])
+        );
+        assert_eq!(
+            body["messages"],
+            json!([
+                { "role": "user", "content": [{ "type": "text", "text": "Weather in Oslo?" }] },
+                {
+                    "role": "assistant",
+                    "content": [
+                        thinking,
+                        { "type": "text", "text": "Checking." },
+                        {
+                            "type": "tool_use",
+                            "id": "call_1",
+                            "name": "get_weather",
+                            "input": { "city": "Oslo" }
+                        }
+                    ]
+                },
+                {
+                    "role": "user",
+                    "content": [{
+                        "type": "tool_result",
+                        "tool_use_id": "call_1",
+                        "content": "Sunny, 21C"
+                    }]
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn anthropic_stream_deltas() {
+        let adapter = ChatProviderType::Anthropic;
+        let events = [
+            event(
+                "content_block_start",
+                json!({
+                    "type": "content_block_start",
+                    "index": 0,
+                    "content_block": { "type": "thinking", "thinking": "" }
+                }),
+            ),
+            event(
+                "content_block_delta",
+                json!({
+                    "type": "content_block_delta",
+                    "index": 0,
+                    "delta": { "type": "thinking_delta", "thinking": "Need the tool." }
+                }),
+            ),
+            event(
+                "content_block_delta",
+                json!({
+                    "type": "content_block_delta",
+                    "index": 0,
+                    "delta": { "type": "signature_delta", "signature": "sig" }
+                }),
+            ),
+            event(
+                "content_block_delta",
+                json!({
+                    "type": "content_block_delta",
+                    "index": 1,
+                    "delta": { "type": "text_delta", "text": "Checking." }
+                }),
+            ),
+            event(
+                "content_block_start",
+                json!({
+                    "type": "content_block_start",
+                    "index": 2,
+                    "content_block": { "type": "tool_use", "id": "toolu_1", "name": "get_weather" }
+                }),
+            ),
+            event(
+                "content_block_delta",
+                json!({
+                    "type": "content_block_delta",
+                    "index": 2,
+                    "delta": { "type": "input_json_delta", "partial_json": "{\"city\":" }
+                }),
+            ),
+            event(
+                "content_block_delta",
+                json!({
+                    "type": "content_block_delta",
+                    "index": 2,
+                    "delta": { "type": "input_json_delta", "partial_json": "\"Oslo\"}" }
+                }),
+            ),
+        ];
+
+        let mut content = String::new();
+        let mut reasoning = String::new();
+        let mut accumulator = ToolCallAccumulator::default();
+        for event in &events {
+            let update = adapter.parse_event(event);
+            content.extend(update.content);
+            reasoning.extend(update.reasoning);
+            update.tool_calls.into_iter().for_each(|delta| accumulator.push(delta));
+            update.thinking.into_iter().for_each(|delta| accumulator.push_thinking(delta));
+        }
+
+        assert_eq!(content, "Checking.");
+        assert_eq!(reasoning, "Need the tool.");
+        let tool_calls = accumulator.finish();
+        assert_eq!(tool_calls.calls.len(), 1);
+        assert_eq!(tool_calls.calls[0].id, "toolu_1");
+        assert_eq!(tool_calls.calls[0].name, "get_weather");
+        assert_eq!(tool_calls.calls[0].arguments, r#"{"city":"Oslo"}"#);
+        assert_eq!(
+            tool_calls.thinking,
+            [json!({ "type": "thinking", "thinking": "Need the tool.", "signature": "sig" })]
+        );
+    }
+
+    #[test]
+    fn anthropic_usage_and_stop() {
+        let adapter = ChatProviderType::Anthropic;
+        let mut usage = None;
+
+        let start = adapter.parse_event(&event(
+            "message_start",
+            json!({ "type": "message_start", "message": { "usage": { "input_tokens": 25 } } }),
+        ));
+        merge_usage(&mut usage, start.usage.unwrap());
+        let delta = adapter.parse_event(&event(
+            "message_delta",
+            json!({
+                "type": "message_delta",
+                "delta": { "stop_reason": "end_turn" },
+                "usage": { "output_tokens": 12 }
+            }),
+        ));
+        merge_usage(&mut usage, delta.usage.unwrap());
+
+        assert_eq!(
+            usage,
+            Some(json!({ "prompt_tokens": 25, "completion_tokens": 12, "total_tokens": 37 }))
+        );
+        assert!(adapter.parse_event(&event("message_stop", json!({ "type": "message_stop" }))).done);
+        let error = adapter.parse_event(&event(
+            "error",
+            json!({ "type": "error", "error": { "message": "Overloaded" } }),
+        ));
+        assert_eq!(error.error.as_deref(), Some("Overloaded"));
+    }
+
+    #[test]
+    fn gemini_maps_contents_and_system_instruction() {
+        let input = ChatInput {
+            system_prompt: Some("Be brief.".to_string()),
+            history: vec![
+                json!({ "role": "system", "content": "Answer in English." }),
+                json!({ "role": "user", "content": "Hi" }),
+                json!({ "role": "assistant", "content": "Hello!" }),
+            ],
+            user_message: "Weather?".to_string(),
+            ..Default::default()
+        };
+
+        let body = ChatProviderType::Gemini.build_body("gemini-2.5-flash", &input);
+        assert_eq!(
+            body["system_instruction"],
+            json!({ "parts": [{ "text": "Be brief.\n\nAnswer in English." }] })
+        );
+        assert_eq!(
+            body["contents"],
+            json!([
+                { "role": "user", "parts": [{ "text": "Hi" }] },
+                { "role": "model", "parts": [{ "text": "Hello!" }] },
+                { "role": "user", "parts": [{ "text": "Weather?" }] },
+            ])
+        );
+        // The model goes in the url, not the body
+        assert!(body.get("model").is_none());
+    }
+
+    #[test]
+    fn gemini_sends_images_as_inline_data() {
+        let input = ChatInput {
+            history: vec![json!({
+                "role": "user",
+                "content": [
+                    { "type": "image_url", "image_url": { "url": "data:image/jpeg;base64,BBBB" } }
+                ]
+            })],
+            user_message: "What is this?".to_string(),
+            images: vec![ChatImage {
+                mime: "image/png".to_string(),
+                data: "AAAA".to_string(),
+            }],
+            ..Default::default()
+        };
+
+        let body = ChatProviderType::Gemini.build_body("gemini-2.5-flash", &input);
+        assert_eq!(
+            body["contents"],
+            json!([{
+                "role": "user",
+                "parts": [
+                    { "inline_data": { "mime_type": "image/jpeg", "data": "BBBB" } },
+                    { "text": "What is this?" },
+                    { "inline_data": { "mime_type": "image/png", "data": "AAAA" } },
+                ]
+            }])
+        );
+    }
+
+    #[test]
+    fn gemini_function_call_round_trip_keeps_thought_signature() {
+        let adapter = ChatProviderType::Gemini;
+        let update = adapter.parse_event(&event(
+            "message",
+            json!({
+                "candidates": [{
+                    "content": {
+                        "role": "model",
+                        "parts": [{
+                            "functionCall": { "name": "get_weather", "args": { "city": "Oslo" } },
+                            "thoughtSignature": "sig"
+                        }]
+                    }
+                }]
+            }),
+        ));
+        let mut accumulator = ToolCallAccumulator::default();
+        update.tool_calls.into_iter().for_each(|delta| accumulator.push(delta));
+        let tool_calls = accumulator.finish();
+        let call = &tool_calls.calls[0];
+        // Gemini sends no id, so one is made up to match the result back
+        assert_eq!(call.id, "call_0");
+        assert_eq!(call.arguments, r#"{"city":"Oslo"}"#);
+        assert_eq!(call.thought_signature.as_deref(), Some("sig"));
+
+        let mut input = ChatInput {
+            user_message: "Weather in Oslo?".to_string(),
+            tools: vec![weather_tool()],
+            ..Default::default()
+        };
+        input.commit_user_turn();
+        input.push_assistant_tool_calls("", &tool_calls);
+        input.push_tool_result(call, "Sunny, 21C".to_string());
+
+        let body = adapter.build_body("gemini-2.5-flash", &input);
+        assert_eq!(
+            body["tools"],
+            json!([{ "functionDeclarations": [weather_tool()["function"]] }])
+        );
+        assert_eq!(
+            body["contents"],
+            json!([
+                { "role": "user", "parts": [{ "text": "Weather in Oslo?" }] },
+                {
+                    "role": "model",
+                    "parts": [{
+                        "functionCall": { "name": "get_weather", "args": { "city": "Oslo" } },
+                        "thoughtSignature": "sig"
+                    }]
+                },
+                {
+                    "role": "user",
+                    "parts": [{
+                        "functionResponse": {
+                            "name": "get_weather",
+                            "response": { "content": "Sunny, 21C" }
+                        }
+                    }]
+                },
+            ])
+        );
+
+        // Other providers never see the signature
+        let openai = ChatProviderType::OpenAi.build_body("gpt", &input);
+        assert!(openai["messages"][1]["tool_calls"][0].get("thought_signature").is_none());
+    }
+
+    #[test]
+    fn gemini_text_thoughts_and_usage() {
+        let update = ChatProviderType::Gemini.parse_event(&event(
+            "message",
+            json!({
+                "candidates": [{
+                    "content": {
+                        "parts": [
+                            { "text": "Thinking it over.", "thought": true },
+                            { "text": "It's sunny." }
+                        ]
+                    }
+                }],
+                "usageMetadata": {
+                    "promptTokenCount": 30,
+                    "candidatesTokenCount": 8,
+                    "totalTokenCount": 45
+                }
+            }),
+        ));
+
+        assert_eq!(update.content.as_deref(), Some("It's sunny."));
+        assert_eq!(update.reasoning.as_deref(), Some("Thinking it over."));
+        // Gemini's total includes thinking tokens, so it's kept as reported
+        assert_eq!(
+            update.usage,
+            Some(json!({ "prompt_tokens": 30, "completion_tokens": 8, "total_tokens": 45 }))
+        );
+    }
+
+    #[test]
+    fn gemini_stream_url() {
+        let gemini = ChatProviderType::Gemini;
+        let base = "https://generativelanguage.googleapis.com/v1beta";
+        let full = format!("{}/models/gemini-2.5-flash:streamGenerateContent?alt=sse", base);
+
+        assert_eq!(gemini.stream_url(base, "gemini-2.5-flash"), full);
+        assert_eq!(gemini.stream_url(&format!("{}/", base), "gemini-2.5-flash"), full);
+        assert_eq!(gemini.stream_url(&full, "ignored"), full);
+        assert_eq!(
+            gemini.stream_url(&format!("{}/models/m:streamGenerateContent?key=k", base), "m"),
+            format!("{}/models/m:streamGenerateContent?key=k&alt=sse", base)
+        );
+        assert_eq!(
+            ChatProviderType::OpenAi.stream_url("https://api.openai.com/v1/chat/completions", "gpt"),
+            "https://api.openai.com/v1/chat/completions"
+        );
+    }
+}
@@ -0,0 +1,118 @@
+// Ordered list of chat models to try when the selected one fails with a
+// transient error: connection failure, 5xx, rate limit, or a stream that dies
+// before its first token. Mirrors the primary/fallback pair transcription
+// already gets from UserAudioConfig, but for chat and user-configured.
+use crate::chat_adapters::ChatProviderType;
+use crate::config_store::{JsonConfig, JsonConfigStore};
+use crate::direct_provider::DirectProviderConfig;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+const MAX_ENTRIES: usize = 5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChatFallbackEntry {
+    // A model served through the license server, like the selected model
+    Hosted { provider: String, model: String },
+    // A local or self-managed endpoint, configured like direct mode
+    Direct {
+        base_url: String,
+        #[serde(default)]
+        api_key: String,
+        model: String,
+        #[serde(default)]
+        provider_type: ChatProviderType,
+    },
+}
+
+impl ChatFallbackEntry {
+    pub fn label(&self) -> String {
+        match self {
+            ChatFallbackEntry::Hosted { provider, model } => format!("{}/{}", provider, model),
+            ChatFallbackEntry::Direct { base_url, model, .. } => format!("{} ({})", model, base_url),
+        }
+    }
+
+    pub fn direct_config(&self) -> Option<DirectProviderConfig> {
+        match self {
+            ChatFallbackEntry::Hosted { .. } => None,
+            ChatFallbackEntry::Direct {
+                base_url,
+                api_key,
+                model,
+                provider_type,
+            } => Some(DirectProviderConfig {
+                enabled: true,
+                base_url: base_url.clone(),
+                api_key: api_key.clone(),
+                model: model.clone(),
+                provider_type: *provider_type,
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChatFallbackConfig {
+    pub enabled: bool,
+    // Tried in order after the primary model
+    pub entries: Vec<ChatFallbackEntry>,
+}
+
+impl JsonConfig for ChatFallbackConfig {
+    const FILE_NAME: &'static str = "chat_fallback.json";
+    const LABEL: &'static str = "chat fallback";
+}
+
+#[derive(Default)]
+pub struct ChatFallbackState {
+    config: JsonConfigStore<ChatFallbackConfig>,
+}
+
+fn load_config(app: &AppHandle) -> Result<ChatFallbackConfig, String> {
+    app.state::<ChatFallbackState>().config.load(app)
+}
+
+/// Fallback entries to try in order, empty when the chain is switched off.
+pub fn active_entries(app: &AppHandle) -> Vec<ChatFallbackEntry> {
+    match load_config(app) {
+        Ok(config) if config.enabled => config.entries,
+        Ok(_) => Vec::new(),
+        Err(e) => {
+            tracing::warn!("Failed to load chat fallback config: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+#[tauri::command]
+pub fn get_chat_fallback_config(app: AppHandle) -> Result<ChatFallbackConfig, String> {
+    load_config(&app)
+}
+
+#[tauri::command]
+pub fn update_chat_fallback_config(app: AppHandle, config: ChatFallbackConfig) -> Result<(), String> {
+    if config.entries.len() > MAX_ENTRIES {
+        return Err(format!("Invalid entries: must be at most {}", MAX_ENTRIES));
+    }
+    for entry in &config.entries {
+        match entry {
+            ChatFallbackEntry::Hosted { provider, model } => {
+                if provider.trim().is_empty() || model.trim().is_empty() {
+                    return Err("Invalid entry: provider and model must not be empty".to_string());
+                }
+            }
+            ChatFallbackEntry::Direct { base_url, model, .. } => {
+                if !base_url.trim().starts_with("http") {
+                    return Err("Invalid base_url: must be an http(s) URL".to_string());
+                }
+                if model.trim().is_empty() {
+                    return Err("Invalid entry: model must not be empty".to_string());
+                }
+            }
+        }
+    }
+
+    app.state::<ChatFallbackState>().config.save(&app, config)
+}
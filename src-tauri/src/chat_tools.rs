@@ -0,0 +1,195 @@
+// Tool calling for chat streaming. Definitions use the OpenAI function format
+// and the chat adapters translate them per provider. Built-in tools run here
+// and their results are fed back to the model for a follow-up turn; calls to
+// any other tool are left to the frontend via the `chat_tool_call` event.
+use crate::capture;
+use crate::chat_adapters::{ChatImage, ToolCall};
+use crate::db;
+use crate::image_pipeline;
+use crate::transcript_log;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+pub const TAKE_SCREENSHOT: &str = "take_screenshot";
+pub const READ_LAST_TRANSCRIPT: &str = "read_last_transcript";
+pub const SEARCH_CHAT_HISTORY: &str = "search_chat_history";
+
+// Follow-up turns per request before tool results stop being fed back
+pub const MAX_TOOL_ROUNDS: usize = 4;
+
+const DEFAULT_TRANSCRIPT_MINUTES: u64 = 5;
+const MAX_TRANSCRIPT_MINUTES: u64 = 120;
+const DEFAULT_SEARCH_LIMIT: u64 = 5;
+const MAX_SEARCH_LIMIT: u64 = 20;
+// Long messages are cut so a search can't flood the context window
+const SEARCH_SNIPPET_CHARS: usize = 500;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatToolCallEvent {
+    pub request_id: String,
+    pub id: String,
+    pub name: String,
+    // Parsed arguments; the raw string if the model produced invalid JSON
+    pub arguments: Value,
+    // True if Rust runs the tool and continues the conversation itself
+    pub builtin: bool,
+}
+
+impl ChatToolCallEvent {
+    pub fn new(request_id: &str, call: &ToolCall, builtin: bool) -> Self {
+        Self {
+            request_id: request_id.to_string(),
+            id: call.id.clone(),
+            name: call.name.clone(),
+            arguments: serde_json::from_str(&call.arguments)
+                .unwrap_or_else(|_| Value::String(call.arguments.clone())),
+            builtin,
+        }
+    }
+}
+
+pub struct ToolOutput {
+    pub text: String,
+    // Screenshots go back to the model as an image, not as base64 text
+    pub image: Option<ChatImage>,
+}
+
+pub fn builtin_definitions() -> Vec<Value> {
+    vec![
+        json!({
+            "type": "function",
+            "function": {
+                "name": TAKE_SCREENSHOT,
+                "description": "Capture the user's current screen. Use this when the question is about something visible on screen.",
+                "parameters": { "type": "object", "properties": {} }
+            }
+        }),
+        json!({
+            "type": "function",
+            "function": {
+                "name": READ_LAST_TRANSCRIPT,
+                "description": "Read the live conversation transcript (meeting or call audio) from the last few minutes.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "minutes": {
+                            "type": "integer",
+                            "description": "How many minutes back to read (default 5, max 120)."
+                        }
+                    }
+                }
+            }
+        }),
+        json!({
+            "type": "function",
+            "function": {
+                "name": SEARCH_CHAT_HISTORY,
+                "description": "Search the user's saved chat conversations for messages containing the given text.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "query": {
+                            "type": "string",
+                            "description": "Text to search for."
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "Maximum number of messages to return (default 5, max 20)."
+                        }
+                    },
+                    "required": ["query"]
+                }
+            }
+        }),
+    ]
+}
+
+pub fn is_builtin(name: &str) -> bool {
+    matches!(name, TAKE_SCREENSHOT | READ_LAST_TRANSCRIPT | SEARCH_CHAT_HISTORY)
+}
+
+/// Run a built-in tool. Failures are returned as the tool result so the
+/// model can tell the user instead of the whole request failing.
+pub async fn run_builtin(app: &AppHandle, request_id: &str, call: &ToolCall) -> ToolOutput {
+    let arguments: Value = serde_json::from_str(&call.arguments).unwrap_or_else(|_| json!({}));
+
+    let result = match call.name.as_str() {
+        TAKE_SCREENSHOT => take_screenshot(app, request_id).await,
+        READ_LAST_TRANSCRIPT => Ok(read_last_transcript(app, &arguments)),
+        SEARCH_CHAT_HISTORY => search_chat_history(app, &arguments).await,
+        other => Err(format!("Unknown tool: {}", other)),
+    };
+
+    result.unwrap_or_else(|e| ToolOutput {
+        text: format!("Error: {}", e),
+        image: None,
+    })
+}
+
+async fn take_screenshot(app: &AppHandle, request_id: &str) -> Result<ToolOutput, String> {
+    let window = app
+        .get_webview_window("main")
+        .ok_or_else(|| "Main window not found".to_string())?;
+    let base64 = capture::capture_to_base64(window).await?;
+    let image = image_pipeline::prepare_images(app, request_id, vec![base64])
+        .await
+        .pop();
+
+    Ok(ToolOutput {
+        text: "Screenshot captured; it is attached in the next message.".to_string(),
+        image,
+    })
+}
+
+fn read_last_transcript(app: &AppHandle, arguments: &Value) -> ToolOutput {
+    let minutes = arguments
+        .get("minutes")
+        .and_then(|m| m.as_u64())
+        .unwrap_or(DEFAULT_TRANSCRIPT_MINUTES)
+        .clamp(1, MAX_TRANSCRIPT_MINUTES);
+    let entries = transcript_log::recent(app, Duration::from_secs(minutes * 60));
+
+    let text = if entries.is_empty() {
+        format!("No transcript was captured in the last {} minutes.", minutes)
+    } else {
+        transcript_log::format_entries(&entries)
+    };
+    ToolOutput { text, image: None }
+}
+
+async fn search_chat_history(app: &AppHandle, arguments: &Value) -> Result<ToolOutput, String> {
+    let query = arguments
+        .get("query")
+        .and_then(|q| q.as_str())
+        .map(|q| q.trim())
+        .filter(|q| !q.is_empty())
+        .ok_or_else(|| "query is required".to_string())?;
+    let limit = arguments
+        .get("limit")
+        .and_then(|l| l.as_u64())
+        .unwrap_or(DEFAULT_SEARCH_LIMIT)
+        .clamp(1, MAX_SEARCH_LIMIT);
+
+    let matches = db::search_messages(app, query, limit as u32).await?;
+    if matches.is_empty() {
+        return Ok(ToolOutput {
+            text: format!("No saved messages match \"{}\".", query),
+            image: None,
+        });
+    }
+
+    let text = matches
+        .iter()
+        .map(|found| {
+            let mut content: String = found.content.chars().take(SEARCH_SNIPPET_CHARS).collect();
+            if content.len() < found.content.len() {
+                content.push_str("...");
+            }
+            format!("[{}] {}: {}", found.conversation_title, found.role, content)
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    Ok(ToolOutput { text, image: None })
+}
@@ -0,0 +1,94 @@
+// Settings files in the app data dir. Each one is read on first use and then
+// served from memory; saving writes the file and replaces the cached copy.
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+/// A settings type stored as a pretty-printed JSON file.
+pub trait JsonConfig: Clone + Default + Serialize + DeserializeOwned {
+    // File name in the app data dir, e.g. "chat_fallback.json"
+    const FILE_NAME: &'static str;
+    // What the settings are called in error messages, e.g. "chat fallback"
+    const LABEL: &'static str;
+}
+
+pub struct JsonConfigStore<T> {
+    cached: Mutex<Option<T>>,
+}
+
+impl<T> Default for JsonConfigStore<T> {
+    fn default() -> Self {
+        Self {
+            cached: Mutex::new(None),
+        }
+    }
+}
+
+pub fn app_data_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let app_data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+
+    fs::create_dir_all(&app_data_dir)
+        .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+
+    Ok(app_data_dir)
+}
+
+impl<T: JsonConfig> JsonConfigStore<T> {
+    fn path(app: &AppHandle) -> Result<PathBuf, String> {
+        Ok(app_data_dir(app)?.join(T::FILE_NAME))
+    }
+
+    /// The saved settings, or the defaults if the file doesn't exist yet.
+    pub fn load(&self, app: &AppHandle) -> Result<T, String> {
+        let mut cached = self
+            .cached
+            .lock()
+            .map_err(|e| format!("Failed to acquire {} lock: {}", T::LABEL, e))?;
+
+        if let Some(config) = cached.as_ref() {
+            return Ok(config.clone());
+        }
+
+        let path = Self::path(app)?;
+        let config = if path.exists() {
+            let content = fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read {} file: {}", T::LABEL, e))?;
+            serde_json::from_str(&content)
+                .map_err(|e| format!("Failed to parse {} file: {}", T::LABEL, e))?
+        } else {
+            T::default()
+        };
+
+        *cached = Some(config.clone());
+        Ok(config)
+    }
+
+    /// Like `load`, but a broken file is logged and read as the defaults so
+    /// it never blocks the feature that reads it.
+    pub fn load_or_default(&self, app: &AppHandle) -> T {
+        self.load(app).unwrap_or_else(|e| {
+            tracing::warn!("Failed to load {} settings: {}", T::LABEL, e);
+            T::default()
+        })
+    }
+
+    pub fn save(&self, app: &AppHandle, config: T) -> Result<(), String> {
+        let path = Self::path(app)?;
+        let content = serde_json::to_string_pretty(&config)
+            .map_err(|e| format!("Failed to serialize {} settings: {}", T::LABEL, e))?;
+        fs::write(&path, content)
+            .map_err(|e| format!("Failed to write {} file: {}", T::LABEL, e))?;
+
+        *self
+            .cached
+            .lock()
+            .map_err(|e| format!("Failed to acquire {} lock: {}", T::LABEL, e))? = Some(config);
+        Ok(())
+    }
+}
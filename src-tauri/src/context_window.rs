@@ -0,0 +1,472 @@
+// Keeps chat requests inside the model's context window. Long conversations
+// used to be sent whole until the provider rejected them; now the oldest
+// turns are dropped (or condensed into a short digest) to fit a token budget.
+// Token counts are estimates: ~4 characters per token for text and OpenAI's
+// tile formula for images, which is close enough for every provider we use.
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::Cursor;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::chat_adapters::{self, ChatImage, ChatInput};
+use crate::config_store::{JsonConfig, JsonConfigStore};
+
+// Fallback for models missing from the table and the user's overrides
+const DEFAULT_CONTEXT_TOKENS: u32 = 32_768;
+// Role markers and message framing
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+// Image of unknown size: a 2x3 tile screenshot
+const DEFAULT_IMAGE_TOKENS: usize = 1105;
+// Share of the budget the digest of dropped turns may take
+const SUMMARY_BUDGET_DIVISOR: usize = 10;
+const SUMMARY_LINE_CHARS: usize = 200;
+
+// Context limits by model-name prefix; the longest matching prefix wins
+const MODEL_CONTEXT_LIMITS: &[(&str, u32)] = &[
+    ("gpt-5", 400_000),
+    ("gpt-4.1", 1_047_576),
+    ("gpt-4o", 128_000),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4", 8_192),
+    ("gpt-3.5-turbo", 16_385),
+    ("o1", 200_000),
+    ("o3", 200_000),
+    ("o4", 200_000),
+    ("claude", 200_000),
+    ("gemini-1.5-pro", 2_097_152),
+    ("gemini", 1_048_576),
+    ("llama3.", 128_000),
+    ("llama-3.", 128_000),
+    ("llama3", 8_192),
+    ("mistral", 32_768),
+    ("mixtral", 32_768),
+    ("qwen", 32_768),
+    ("deepseek", 64_000),
+    ("grok", 131_072),
+];
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ContextStrategy {
+    // Drop the oldest turns
+    Trim,
+    // Drop the oldest turns but keep a condensed digest of them (first lines
+    // of each message, no extra model call)
+    Summarize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextConfig {
+    pub enabled: bool,
+    pub strategy: ContextStrategy,
+    // Optional cap below the model's own limit, e.g. to keep costs down
+    pub max_context_tokens: Option<u32>,
+    pub reserve_output_tokens: u32,
+    // Context limits by model-name prefix, checked before the built-in table
+    #[serde(default)]
+    pub model_limits: HashMap<String, u32>,
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            strategy: ContextStrategy::Trim,
+            max_context_tokens: None,
+            reserve_output_tokens: 4096, // Room for the answer
+            model_limits: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ContextTrimmed {
+    pub request_id: String,
+    pub model: String,
+    pub budget_tokens: usize,
+    // Estimate for the request as sent, after trimming
+    pub estimated_tokens: usize,
+    pub dropped_messages: usize,
+    pub dropped_tokens: usize,
+    pub summarized: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ContextEstimate {
+    pub estimated_tokens: usize,
+    pub context_limit: u32,
+    pub budget_tokens: usize,
+    pub fits: bool,
+}
+
+impl JsonConfig for ContextConfig {
+    const FILE_NAME: &'static str = "context_window.json";
+    const LABEL: &'static str = "context window";
+}
+
+#[derive(Default)]
+pub struct ContextWindowState {
+    config: JsonConfigStore<ContextConfig>,
+}
+
+fn load_config(app: &AppHandle) -> Result<ContextConfig, String> {
+    app.state::<ContextWindowState>().config.load(app)
+}
+
+/// Context window of `model`, from the user's overrides or the built-in table.
+pub fn context_limit(config: &ContextConfig, model: &str) -> u32 {
+    // "openai/gpt-4o" and "models/gemini-2.0-flash" name the same models
+    let name = model.rsplit('/').next().unwrap_or(model).to_lowercase();
+    let overrides = config
+        .model_limits
+        .iter()
+        .map(|(prefix, limit)| (prefix.as_str(), *limit));
+
+    longest_prefix_limit(&name, overrides)
+        .or_else(|| longest_prefix_limit(&name, MODEL_CONTEXT_LIMITS.iter().copied()))
+        .unwrap_or(DEFAULT_CONTEXT_TOKENS)
+}
+
+fn longest_prefix_limit<'a>(name: &str, limits: impl Iterator<Item = (&'a str, u32)>) -> Option<u32> {
+    limits
+        .filter(|(prefix, _)| name.starts_with(&prefix.to_lowercase()))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, limit)| limit)
+}
+
+fn budget_for(config: &ContextConfig, limit: u32) -> usize {
+    let limit = config.max_context_tokens.map_or(limit, |cap| cap.min(limit));
+    limit.saturating_sub(config.reserve_output_tokens) as usize
+}
+
+/// Estimated tokens of plain text.
+pub fn text_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+// OpenAI high-detail pricing: fit in 2048x2048, shortest side to 768, 170 per 512px tile
+fn image_tokens(width: u32, height: u32) -> usize {
+    if width == 0 || height == 0 {
+        return DEFAULT_IMAGE_TOKENS;
+    }
+
+    let (mut w, mut h) = (width as f64, height as f64);
+    let fit = (2048.0 / w.max(h)).min(1.0);
+    w *= fit;
+    h *= fit;
+    let shorten = (768.0 / w.min(h)).min(1.0);
+    w *= shorten;
+    h *= shorten;
+
+    let tiles = (w / 512.0).ceil() * (h / 512.0).ceil();
+    85 + 170 * tiles as usize
+}
+
+// Decodes just enough of the image to read its header
+fn base64_image_tokens(data: &str) -> usize {
+    let prefix_len = data.len().min(65_536) / 4 * 4;
+    // Bytes, not a str slice: junk input may not split on a char boundary
+    general_purpose::STANDARD
+        .decode(&data.as_bytes()[..prefix_len])
+        .ok()
+        .and_then(|bytes| {
+            image::ImageReader::new(Cursor::new(bytes))
+                .with_guessed_format()
+                .ok()?
+                .into_dimensions()
+                .ok()
+        })
+        .map_or(DEFAULT_IMAGE_TOKENS, |(width, height)| image_tokens(width, height))
+}
+
+/// Estimated tokens of one OpenAI-format message.
+pub fn message_tokens(message: &Value) -> usize {
+    let mut tokens = MESSAGE_OVERHEAD_TOKENS;
+
+    match message.get("content") {
+        Some(Value::Array(parts)) => {
+            for part in parts {
+                match part.get("type").and_then(|t| t.as_str()) {
+                    Some("image_url") => {
+                        let url = part
+                            .pointer("/image_url/url")
+                            .and_then(|u| u.as_str())
+                            .unwrap_or_default();
+                        let data = url.split_once(";base64,").map_or(url, |(_, data)| data);
+                        tokens += base64_image_tokens(data);
+                    }
+                    _ => {
+                        tokens += text_tokens(part.get("text").and_then(|t| t.as_str()).unwrap_or_default());
+                    }
+                }
+            }
+        }
+        Some(content) => tokens += text_tokens(&chat_adapters::openai_content_text(content)),
+        None => {}
+    }
+    if let Some(tool_calls) = message.get("tool_calls") {
+        tokens += text_tokens(&tool_calls.to_string());
+    }
+    tokens
+}
+
+fn images_tokens(images: &[ChatImage]) -> usize {
+    images.iter().map(|image| base64_image_tokens(&image.data)).sum()
+}
+
+/// Estimated tokens of the whole request as the adapters would send it.
+pub fn estimate_input_tokens(input: &ChatInput) -> usize {
+    fixed_tokens(input) + input.history.iter().map(message_tokens).sum::<usize>()
+}
+
+// Everything that can't be trimmed: system prompt, tools and the new user turn
+fn fixed_tokens(input: &ChatInput) -> usize {
+    let system = input
+        .system_prompt
+        .as_deref()
+        .map_or(0, |prompt| MESSAGE_OVERHEAD_TOKENS + text_tokens(prompt));
+    let tools: usize = input.tools.iter().map(|tool| text_tokens(&tool.to_string())).sum();
+    let user = if input.user_message.is_empty() && input.images.is_empty() {
+        0
+    } else {
+        MESSAGE_OVERHEAD_TOKENS + text_tokens(&input.user_message) + images_tokens(&input.images)
+    };
+    system + tools + user
+}
+
+fn role(message: &Value) -> &str {
+    message.get("role").and_then(|r| r.as_str()).unwrap_or("user")
+}
+
+fn summary_line(message: &Value) -> Option<String> {
+    let label = match role(message) {
+        "user" => "User",
+        "assistant" => "Assistant",
+        _ => return None,
+    };
+    let text = chat_adapters::openai_content_text(message.get("content").unwrap_or(&Value::Null));
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() {
+        return None;
+    }
+
+    let mut line: String = text.chars().take(SUMMARY_LINE_CHARS).collect();
+    if line.len() < text.len() {
+        line.push_str("...");
+    }
+    Some(format!("{}: {}", label, line))
+}
+
+// Digest of dropped turns, newest kept when it runs over `max_tokens`
+fn build_summary(dropped: &[Value], max_tokens: usize) -> Option<Value> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut tokens = 0;
+    for line in dropped.iter().rev().filter_map(summary_line) {
+        tokens += text_tokens(&line) + 1;
+        if tokens > max_tokens {
+            break;
+        }
+        lines.push(line);
+    }
+    if lines.is_empty() {
+        return None;
+    }
+    lines.reverse();
+
+    Some(serde_json::json!({
+        "role": "system",
+        "content": format!(
+            "Earlier in this conversation (condensed, older messages omitted):\n{}",
+            lines.join("\n")
+        )
+    }))
+}
+
+struct FitOutcome {
+    dropped_messages: usize,
+    dropped_tokens: usize,
+    summarized: bool,
+}
+
+// Drops whole turns from the front of the history until the request fits.
+// System messages and the current request's own turns are never dropped, and
+// the kept history always starts at a user message so no tool result is orphaned.
+fn fit_history(input: &mut ChatInput, budget: usize, strategy: ContextStrategy) -> Option<FitOutcome> {
+    let history_tokens: Vec<usize> = input.history.iter().map(message_tokens).collect();
+    let total = fixed_tokens(input) + history_tokens.iter().sum::<usize>();
+    if total <= budget {
+        return None;
+    }
+
+    let protected_from = input.turn_start.unwrap_or(input.history.len()).min(input.history.len());
+    let summary_reserve = match strategy {
+        ContextStrategy::Trim => 0,
+        ContextStrategy::Summarize => budget / SUMMARY_BUDGET_DIVISOR,
+    };
+    let target = budget.saturating_sub(summary_reserve);
+
+    // Cut point: drop every non-system message before index `cut`
+    let mut cut = 0;
+    let mut remaining = total;
+    while remaining > target && cut < protected_from {
+        // Advance to the next user message so the history starts a fresh turn
+        let mut next = cut + 1;
+        while next < protected_from && role(&input.history[next]) != "user" {
+            next += 1;
+        }
+        remaining -= input.history[cut..next]
+            .iter()
+            .zip(&history_tokens[cut..next])
+            .filter(|(message, _)| role(message) != "system")
+            .map(|(_, tokens)| tokens)
+            .sum::<usize>();
+        cut = next;
+    }
+    if cut == 0 {
+        return None;
+    }
+
+    let mut dropped = Vec::new();
+    let mut kept = Vec::new();
+    for (index, message) in std::mem::take(&mut input.history).into_iter().enumerate() {
+        if index < cut && role(&message) != "system" {
+            dropped.push(message);
+        } else {
+            kept.push(message);
+        }
+    }
+    let dropped_tokens = total - remaining;
+    let dropped_messages = dropped.len();
+
+    let summary = match strategy {
+        ContextStrategy::Trim => None,
+        ContextStrategy::Summarize => build_summary(&dropped, summary_reserve),
+    };
+    let summarized = summary.is_some();
+    if let Some(summary) = summary {
+        // After the pinned system messages, before the first kept turn
+        let position = kept.iter().take_while(|message| role(message) == "system").count();
+        kept.insert(position, summary);
+    }
+
+    input.turn_start = input.turn_start.map(|start| start - dropped_messages + usize::from(summarized));
+    input.history = kept;
+
+    Some(FitOutcome {
+        dropped_messages,
+        dropped_tokens,
+        summarized,
+    })
+}
+
+/// Trim `input` to fit the context window of `model`, emitting
+/// `chat_context_trimmed` when anything was dropped.
+pub fn fit(app: &AppHandle, request_id: &str, input: &mut ChatInput, model: &str) {
+    let config = config_or_default(app);
+    let limit = context_limit(&config, model);
+    fit_with_limit(app, request_id, input, model, limit, &config);
+}
+
+/// Same as `fit`, for models whose context size is known exactly (on-device).
+pub fn fit_to_context(app: &AppHandle, request_id: &str, input: &mut ChatInput, model: &str, limit: u32) {
+    let config = config_or_default(app);
+    fit_with_limit(app, request_id, input, model, limit, &config);
+}
+
+fn config_or_default(app: &AppHandle) -> ContextConfig {
+    app.state::<ContextWindowState>().config.load_or_default(app)
+}
+
+fn fit_with_limit(
+    app: &AppHandle,
+    request_id: &str,
+    input: &mut ChatInput,
+    model: &str,
+    limit: u32,
+    config: &ContextConfig,
+) {
+    if !config.enabled {
+        return;
+    }
+
+    let budget = budget_for(config, limit);
+    let Some(outcome) = fit_history(input, budget, config.strategy) else {
+        return;
+    };
+
+    let estimated_tokens = estimate_input_tokens(input);
+    tracing::info!(
+        "Trimmed {} messages (~{} tokens) to fit {} into {} tokens",
+        outcome.dropped_messages,
+        outcome.dropped_tokens,
+        model,
+        budget
+    );
+    let _ = app.emit(
+        "chat_context_trimmed",
+        ContextTrimmed {
+            request_id: request_id.to_string(),
+            model: model.to_string(),
+            budget_tokens: budget,
+            estimated_tokens,
+            dropped_messages: outcome.dropped_messages,
+            dropped_tokens: outcome.dropped_tokens,
+            summarized: outcome.summarized,
+        },
+    );
+}
+
+#[tauri::command]
+pub fn get_context_config(app: AppHandle) -> Result<ContextConfig, String> {
+    load_config(&app)
+}
+
+#[tauri::command]
+pub fn update_context_config(app: AppHandle, config: ContextConfig) -> Result<(), String> {
+    if !(256..=65_536).contains(&config.reserve_output_tokens) {
+        return Err("Invalid reserve_output_tokens: must be between 256 and 65536".to_string());
+    }
+    if config.max_context_tokens.is_some_and(|max| max <= config.reserve_output_tokens) {
+        return Err("Invalid max_context_tokens: must be greater than reserve_output_tokens".to_string());
+    }
+    if config.model_limits.values().any(|limit| *limit < 1024) {
+        return Err("Invalid model_limits: each limit must be at least 1024".to_string());
+    }
+
+    app.state::<ContextWindowState>().config.save(&app, config)
+}
+
+// Lets the UI show how full the context is before sending
+#[tauri::command]
+pub fn estimate_chat_context(
+    app: AppHandle,
+    model: String,
+    user_message: String,
+    system_prompt: Option<String>,
+    history: Option<String>,
+) -> Result<ContextEstimate, String> {
+    let config = load_config(&app)?;
+    let history = match history {
+        Some(history) => serde_json::from_str::<Vec<Value>>(&history)
+            .map_err(|e| format!("Invalid history: {}", e))?,
+        None => Vec::new(),
+    };
+    let input = ChatInput {
+        system_prompt,
+        user_message,
+        history,
+        ..Default::default()
+    };
+
+    let context_limit = context_limit(&config, &model);
+    let budget_tokens = budget_for(&config, context_limit);
+    let estimated_tokens = estimate_input_tokens(&input);
+    Ok(ContextEstimate {
+        estimated_tokens,
+        context_limit,
+        budget_tokens,
+        fits: estimated_tokens <= budget_tokens,
+    })
+}
@@ -1,4 +1,10 @@
-use tauri_plugin_sql::{Migration, MigrationKind};
+use serde::Serialize;
+use sqlx::{Row, SqlitePool};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_sql::{DbInstances, DbPool, Migration, MigrationKind};
+
+// Preloaded by the SQL plugin (tauri.conf.json) and shared with the frontend
+pub const DB_URL: &str = "sqlite:pluely.db";
 
 /// Returns all database migrations
 pub fn migrations() -> Vec<Migration> {
@@ -17,5 +23,74 @@ pub fn migrations() -> Vec<Migration> {
             sql: include_str!("migrations/chat-history.sql"),
             kind: MigrationKind::Up,
         },
+        // Migration 3: Create usage ledger table
+        Migration {
+            version: 3,
+            description: "create_usage_records_table",
+            sql: include_str!("migrations/usage-ledger.sql"),
+            kind: MigrationKind::Up,
+        },
     ]
 }
+
+/// The SQL plugin's connection pool, so Rust code shares the frontend's database.
+pub async fn sqlite_pool(app: &AppHandle) -> Result<SqlitePool, String> {
+    let instances = app
+        .try_state::<DbInstances>()
+        .ok_or_else(|| "Database plugin is not initialized".to_string())?;
+    let instances = instances.0.read().await;
+    match instances.get(DB_URL) {
+        Some(DbPool::Sqlite(pool)) => Ok(pool.clone()),
+        _ => Err("Database is not loaded".to_string()),
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MessageMatch {
+    pub conversation_id: String,
+    pub conversation_title: String,
+    pub role: String,
+    pub content: String,
+    pub timestamp: i64,
+}
+
+/// Case-insensitive substring search over saved chat messages, newest first.
+pub async fn search_messages(
+    app: &AppHandle,
+    query: &str,
+    limit: u32,
+) -> Result<Vec<MessageMatch>, String> {
+    let pool = sqlite_pool(app).await?;
+    let pattern = format!(
+        "%{}%",
+        query
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    );
+
+    let rows = sqlx::query(
+        "SELECT m.conversation_id, c.title, m.role, m.content, m.timestamp \
+         FROM messages m JOIN conversations c ON c.id = m.conversation_id \
+         WHERE m.content LIKE ? ESCAPE '\\' \
+         ORDER BY m.timestamp DESC LIMIT ?",
+    )
+    .bind(pattern)
+    .bind(limit)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| format!("Failed to search chat history: {}", e))?;
+
+    rows.iter()
+        .map(|row| {
+            Ok(MessageMatch {
+                conversation_id: row.try_get(0)?,
+                conversation_title: row.try_get(1)?,
+                role: row.try_get(2)?,
+                content: row.try_get(3)?,
+                timestamp: row.try_get(4)?,
+            })
+        })
+        .collect::<Result<Vec<_>, sqlx::Error>>()
+        .map_err(|e| format!("Failed to read chat history: {}", e))
+}
@@ -0,0 +1,20 @@
+-- Create usage records table (one row per chat or transcription request)
+CREATE TABLE IF NOT EXISTS usage_records (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    kind TEXT NOT NULL CHECK(kind IN ('chat', 'transcription')),
+    request_id TEXT,
+    provider TEXT,
+    model TEXT NOT NULL,
+    prompt_tokens INTEGER NOT NULL DEFAULT 0,
+    completion_tokens INTEGER NOT NULL DEFAULT 0,
+    audio_seconds REAL NOT NULL DEFAULT 0,
+    latency_ms INTEGER NOT NULL,
+    -- 1 when the provider reported no usage and the token counts are estimates
+    estimated INTEGER NOT NULL DEFAULT 0,
+    timestamp INTEGER NOT NULL
+);
+
+-- Indexes for faster lookups
+CREATE INDEX IF NOT EXISTS idx_usage_records_timestamp ON usage_records(timestamp);
+-- Composite index for per-kind aggregates
+CREATE INDEX IF NOT EXISTS idx_usage_records_kind_timestamp ON usage_records(kind, timestamp);
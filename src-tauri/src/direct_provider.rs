@@ -0,0 +1,155 @@
+// Direct provider mode: chat goes straight to a locally configured endpoint
+// (Ollama, llama.cpp, LM Studio or any hosted API) instead of asking the
+// license server for a model config first. Works without a license.
+use crate::chat_adapters::ChatProviderType;
+use crate::config_store::{JsonConfig, JsonConfigStore};
+use crate::http::{self, EndpointKind};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirectProviderConfig {
+    pub enabled: bool,
+    // API base, e.g. http://localhost:11434/v1 for Ollama
+    pub base_url: String,
+    #[serde(default)]
+    pub api_key: String,
+    pub model: String,
+    #[serde(default)]
+    pub provider_type: ChatProviderType,
+}
+
+impl Default for DirectProviderConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            base_url: "http://localhost:11434/v1".to_string(),
+            api_key: String::new(),
+            model: String::new(),
+            provider_type: ChatProviderType::OpenAi,
+        }
+    }
+}
+
+impl DirectProviderConfig {
+    pub fn chat_url(&self) -> String {
+        let base = self.base_url.trim().trim_end_matches('/');
+        match self.provider_type {
+            ChatProviderType::OpenAi => format!("{}/chat/completions", base),
+            ChatProviderType::Anthropic => format!("{}/messages", base),
+            // ChatProviderType::stream_url adds the model and method
+            ChatProviderType::Gemini => base.to_string(),
+        }
+    }
+}
+
+impl JsonConfig for DirectProviderConfig {
+    const FILE_NAME: &'static str = "direct_provider.json";
+    const LABEL: &'static str = "direct provider";
+}
+
+#[derive(Default)]
+pub struct DirectProviderState {
+    config: JsonConfigStore<DirectProviderConfig>,
+}
+
+fn load_config(app: &AppHandle) -> Result<DirectProviderConfig, String> {
+    app.state::<DirectProviderState>().config.load(app)
+}
+
+/// The direct provider config if direct mode is switched on.
+pub fn active_config(app: &AppHandle) -> Option<DirectProviderConfig> {
+    match load_config(app) {
+        Ok(config) if config.enabled => Some(config),
+        Ok(_) => None,
+        Err(e) => {
+            tracing::warn!("Failed to load direct provider config: {}", e);
+            None
+        }
+    }
+}
+
+#[tauri::command]
+pub fn get_direct_provider_config(app: AppHandle) -> Result<DirectProviderConfig, String> {
+    load_config(&app)
+}
+
+#[tauri::command]
+pub fn save_direct_provider_config(
+    app: AppHandle,
+    config: DirectProviderConfig,
+) -> Result<(), String> {
+    if config.enabled {
+        if !config.base_url.trim().starts_with("http") {
+            return Err("Invalid base_url: must be an http(s) URL".to_string());
+        }
+        if config.model.trim().is_empty() {
+            return Err("Choose a model before enabling direct mode".to_string());
+        }
+    }
+
+    app.state::<DirectProviderState>().config.save(&app, config)
+}
+
+// Arguments override the saved config so the settings UI can list models
+// before the user saves anything
+#[tauri::command]
+pub async fn list_local_models(
+    app: AppHandle,
+    base_url: Option<String>,
+    api_key: Option<String>,
+    provider_type: Option<ChatProviderType>,
+) -> Result<Vec<String>, String> {
+    let saved = load_config(&app)?;
+    let base_url = base_url.unwrap_or(saved.base_url);
+    let api_key = api_key.unwrap_or(saved.api_key);
+    let provider_type = provider_type.unwrap_or(saved.provider_type);
+
+    let url = format!("{}/models", base_url.trim().trim_end_matches('/'));
+    let response = http::send(&app, EndpointKind::Chat, &http::provider_key(&url), |client| {
+        let request = client.get(&url);
+        Ok(if api_key.trim().is_empty() {
+            request
+        } else {
+            provider_type.authorize(request, api_key.trim())
+        })
+    })
+    .await
+    .map_err(|e| format!("Failed to reach {}: {}", base_url, e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "Unknown server error".to_string());
+        return Err(format!("Server error ({}): {}", status, error_text));
+    }
+
+    let json: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse models response: {}", e))?;
+
+    // OpenAI/Anthropic: {data: [{id}]}, Ollama native and Gemini: {models: [{name}]}
+    let mut models: Vec<String> = json
+        .get("data")
+        .or_else(|| json.get("models"))
+        .and_then(|list| list.as_array())
+        .map(|list| {
+            list.iter()
+                .filter_map(|model| {
+                    model
+                        .get("id")
+                        .or_else(|| model.get("name"))
+                        .and_then(|id| id.as_str())
+                })
+                .map(|id| id.trim_start_matches("models/").to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    models.sort();
+    models.dedup();
+
+    Ok(models)
+}
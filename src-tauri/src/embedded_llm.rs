@@ -0,0 +1,536 @@
+// On-device chat inference with a quantized GGUF model (llama.cpp), for
+// air-gapped use. Models are imported into <app data>/models; settings and
+// import work in every build, inference needs the `local-llm` feature.
+use crate::chat_adapters::{self, ChatInput};
+use crate::config_store::{self, JsonConfig, JsonConfigStore};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+use tokio::sync::oneshot;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddedLlmConfig {
+    pub enabled: bool,
+    // File name inside the models directory
+    pub model_file: Option<String>,
+    pub context_length: u32,
+    pub threads: u32,
+    pub max_tokens: u32,
+    pub temperature: f32,
+}
+
+impl Default for EmbeddedLlmConfig {
+    fn default() -> Self {
+        let cores = std::thread::available_parallelism()
+            .map(|n| n.get() as u32)
+            .unwrap_or(4);
+        Self {
+            enabled: false,
+            model_file: None,
+            context_length: 4096,        // Fits most small chat models comfortably
+            threads: (cores / 2).max(1), // Leave room for audio capture and the UI
+            max_tokens: 1024,
+            temperature: 0.7,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EmbeddedModelInfo {
+    pub file_name: String,
+    pub size_bytes: u64,
+}
+
+pub enum EmbeddedOutcome {
+    Complete(String),
+    // Text generated before the cancel
+    Cancelled(String),
+}
+
+impl JsonConfig for EmbeddedLlmConfig {
+    const FILE_NAME: &'static str = "embedded_llm.json";
+    const LABEL: &'static str = "embedded model";
+}
+
+#[derive(Default)]
+pub struct EmbeddedLlmState {
+    config: JsonConfigStore<EmbeddedLlmConfig>,
+    #[cfg(feature = "local-llm")]
+    runtime: inference::Runtime,
+}
+
+fn get_models_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let models_dir = config_store::app_data_dir(app)?.join("models");
+    fs::create_dir_all(&models_dir)
+        .map_err(|e| format!("Failed to create models directory: {}", e))?;
+    Ok(models_dir)
+}
+
+fn load_config(app: &AppHandle) -> Result<EmbeddedLlmConfig, String> {
+    app.state::<EmbeddedLlmState>().config.load(app)
+}
+
+fn save_config(app: &AppHandle, config: EmbeddedLlmConfig) -> Result<(), String> {
+    app.state::<EmbeddedLlmState>().config.save(app, config)
+}
+
+/// The embedded model settings if it's selected as the chat provider.
+pub fn active_config(app: &AppHandle) -> Option<EmbeddedLlmConfig> {
+    match load_config(app) {
+        Ok(config) if config.enabled => Some(config),
+        Ok(_) => None,
+        Err(e) => {
+            tracing::warn!("Failed to load embedded model settings: {}", e);
+            None
+        }
+    }
+}
+
+// Model files are addressed by bare name, never by a path outside the models dir
+fn is_plain_file_name(file_name: &str) -> bool {
+    !file_name.is_empty()
+        && !file_name.contains('/')
+        && !file_name.contains('\\')
+        && !file_name.contains("..")
+}
+
+// Every GGUF file starts with these magic bytes
+fn is_gguf(path: &Path) -> bool {
+    let mut magic = [0u8; 4];
+    fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .map(|_| &magic == b"GGUF")
+        .unwrap_or(false)
+}
+
+/// Run a chat turn on the local model, calling `on_chunk` for each piece of
+/// decoded text. Images are ignored: the supported models are text-only.
+pub async fn stream_chat(
+    app: &AppHandle,
+    config: &EmbeddedLlmConfig,
+    input: &ChatInput,
+    abort_rx: &mut oneshot::Receiver<()>,
+    on_chunk: impl FnMut(String),
+) -> Result<EmbeddedOutcome, String> {
+    let model_file = config
+        .model_file
+        .as_ref()
+        .ok_or("No on-device model selected. Import a GGUF model first.")?;
+    let model_path = get_models_dir(app)?.join(model_file);
+    if !model_path.exists() {
+        return Err(format!("On-device model not found: {}", model_file));
+    }
+    if !input.images.is_empty() {
+        tracing::warn!("On-device model is text-only, ignoring attached images");
+    }
+
+    let mut messages: Vec<(String, String)> = Vec::new();
+    // Chat templates often allow a single system message, so the format
+    // instructions share it
+    let system = input
+        .system_prompt
+        .iter()
+        .cloned()
+        .chain(input.response_format.as_ref().map(|format| format.instructions()))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    if !system.is_empty() {
+        messages.push(("system".to_string(), system));
+    }
+    for message in &input.history {
+        let role = message.get("role").and_then(|r| r.as_str()).unwrap_or("user");
+        let content = message
+            .get("content")
+            .map(chat_adapters::openai_content_text)
+            .unwrap_or_default();
+        messages.push((role.to_string(), content));
+    }
+    // Empty once commit_user_turn or push_retry_feedback moved it into the history
+    if !input.user_message.is_empty() {
+        messages.push(("user".to_string(), input.user_message.clone()));
+    }
+
+    run_inference(app, config, model_path, messages, abort_rx, on_chunk).await
+}
+
+#[cfg(feature = "local-llm")]
+async fn run_inference(
+    app: &AppHandle,
+    config: &EmbeddedLlmConfig,
+    model_path: PathBuf,
+    messages: Vec<(String, String)>,
+    abort_rx: &mut oneshot::Receiver<()>,
+    mut on_chunk: impl FnMut(String),
+) -> Result<EmbeddedOutcome, String> {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use tokio::sync::mpsc;
+
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let (chunk_tx, mut chunk_rx) = mpsc::unbounded_channel::<String>();
+    // Loading and generating are both blocking, CPU-bound work
+    let generation = tokio::task::spawn_blocking({
+        let app = app.clone();
+        let config = config.clone();
+        let cancelled = cancelled.clone();
+        move || {
+            let state = app.state::<EmbeddedLlmState>();
+            let (backend, model) = state.runtime.load(&model_path)?;
+            inference::generate(&backend, &model, &config, &messages, &cancelled, chunk_tx)
+        }
+    });
+
+    let mut full_response = String::new();
+    loop {
+        tokio::select! {
+            _ = &mut *abort_rx => {
+                // The blocking loop checks the flag between tokens
+                cancelled.store(true, Ordering::Relaxed);
+                return Ok(EmbeddedOutcome::Cancelled(full_response));
+            }
+            chunk = chunk_rx.recv() => match chunk {
+                Some(chunk) => {
+                    full_response.push_str(&chunk);
+                    on_chunk(chunk);
+                }
+                None => break,
+            },
+        }
+    }
+
+    generation
+        .await
+        .map_err(|e| format!("On-device inference crashed: {}", e))??;
+    Ok(EmbeddedOutcome::Complete(full_response))
+}
+
+#[cfg(not(feature = "local-llm"))]
+async fn run_inference(
+    _app: &AppHandle,
+    _config: &EmbeddedLlmConfig,
+    _model_path: PathBuf,
+    _messages: Vec<(String, String)>,
+    _abort_rx: &mut oneshot::Receiver<()>,
+    _on_chunk: impl FnMut(String),
+) -> Result<EmbeddedOutcome, String> {
+    Err("This build doesn't include on-device inference (built without the local-llm feature)".to_string())
+}
+
+#[cfg(feature = "local-llm")]
+mod inference {
+    use super::EmbeddedLlmConfig;
+    use llama_cpp_2::context::params::LlamaContextParams;
+    use llama_cpp_2::llama_backend::LlamaBackend;
+    use llama_cpp_2::llama_batch::LlamaBatch;
+    use llama_cpp_2::model::params::LlamaModelParams;
+    use llama_cpp_2::model::{AddBos, LlamaChatMessage, LlamaModel, Special};
+    use llama_cpp_2::sampling::LlamaSampler;
+    use std::num::NonZeroU32;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use tokio::sync::mpsc::UnboundedSender;
+
+    // llama.cpp's backend can only be initialised once per process, and
+    // loading a model takes seconds, so both are kept around
+    #[derive(Default)]
+    pub struct Runtime {
+        backend: Mutex<Option<Arc<LlamaBackend>>>,
+        model: Mutex<Option<(PathBuf, Arc<LlamaModel>)>>,
+    }
+
+    impl Runtime {
+        pub fn load(&self, path: &Path) -> Result<(Arc<LlamaBackend>, Arc<LlamaModel>), String> {
+            let backend = {
+                let mut backend = self
+                    .backend
+                    .lock()
+                    .map_err(|e| format!("Failed to acquire inference lock: {}", e))?;
+                match backend.as_ref() {
+                    Some(backend) => backend.clone(),
+                    None => {
+                        let initialised = Arc::new(LlamaBackend::init().map_err(|e| {
+                            format!("Failed to initialise on-device inference: {}", e)
+                        })?);
+                        *backend = Some(initialised.clone());
+                        initialised
+                    }
+                }
+            };
+
+            let mut loaded = self
+                .model
+                .lock()
+                .map_err(|e| format!("Failed to acquire inference lock: {}", e))?;
+            if let Some((loaded_path, model)) = loaded.as_ref() {
+                if loaded_path == path {
+                    return Ok((backend, model.clone()));
+                }
+            }
+
+            // Drop the previous model before loading the next one to cap memory
+            *loaded = None;
+            let model = LlamaModel::load_from_file(&backend, path, &LlamaModelParams::default())
+                .map_err(|e| format!("Failed to load model {}: {}", path.display(), e))?;
+            let model = Arc::new(model);
+            *loaded = Some((path.to_path_buf(), model.clone()));
+            Ok((backend, model))
+        }
+
+        // Forget the cached model if it was loaded from `path`. A generation
+        // already running keeps its own reference until it finishes.
+        pub fn unload(&self, path: &Path) {
+            let mut loaded = match self.model.lock() {
+                Ok(guard) => guard,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            if loaded.as_ref().is_some_and(|(loaded_path, _)| loaded_path == path) {
+                *loaded = None;
+            }
+        }
+    }
+
+    pub fn generate(
+        backend: &LlamaBackend,
+        model: &LlamaModel,
+        config: &EmbeddedLlmConfig,
+        messages: &[(String, String)],
+        cancelled: &AtomicBool,
+        chunk_tx: UnboundedSender<String>,
+    ) -> Result<(), String> {
+        let prompt = build_prompt(model, messages);
+        let tokens = model
+            .str_to_token(&prompt, AddBos::Always)
+            .map_err(|e| format!("Failed to tokenize prompt: {}", e))?;
+
+        let context_length = config.context_length as usize;
+        if tokens.len() + config.max_tokens as usize > context_length {
+            return Err(format!(
+                "Conversation is too long for the on-device model ({} prompt tokens, context length {})",
+                tokens.len(),
+                context_length
+            ));
+        }
+
+        let threads = config.threads as i32;
+        let ctx_params = LlamaContextParams::default()
+            .with_n_ctx(NonZeroU32::new(config.context_length))
+            .with_n_batch(config.context_length)
+            .with_n_threads(threads)
+            .with_n_threads_batch(threads);
+        let mut ctx = model
+            .new_context(backend, ctx_params)
+            .map_err(|e| format!("Failed to create inference context: {}", e))?;
+
+        let mut batch = LlamaBatch::new(context_length, 1);
+        let last_index = tokens.len() as i32 - 1;
+        for (pos, token) in (0_i32..).zip(tokens) {
+            batch
+                .add(token, pos, &[0], pos == last_index)
+                .map_err(|e| format!("Failed to prepare prompt: {}", e))?;
+        }
+        ctx.decode(&mut batch)
+            .map_err(|e| format!("Failed to evaluate prompt: {}", e))?;
+
+        let mut sampler = LlamaSampler::chain_simple([
+            LlamaSampler::temp(config.temperature),
+            LlamaSampler::dist(rand::random::<u32>()),
+        ]);
+
+        let mut position = batch.n_tokens();
+        // Tokens can end mid-character; hold bytes until they form valid UTF-8
+        let mut pending_bytes: Vec<u8> = Vec::new();
+
+        for _ in 0..config.max_tokens {
+            if cancelled.load(Ordering::Relaxed) {
+                break;
+            }
+
+            let token = sampler.sample(&ctx, batch.n_tokens() - 1);
+            sampler.accept(token);
+            if model.is_eog_token(token) {
+                break;
+            }
+
+            let bytes = model
+                .token_to_bytes(token, Special::Tokenize)
+                .map_err(|e| format!("Failed to decode token: {}", e))?;
+            pending_bytes.extend_from_slice(&bytes);
+            let valid_up_to = match std::str::from_utf8(&pending_bytes) {
+                Ok(text) => text.len(),
+                Err(e) => e.valid_up_to(),
+            };
+            if valid_up_to > 0 {
+                let text = String::from_utf8_lossy(&pending_bytes[..valid_up_to]).into_owned();
+                pending_bytes.drain(..valid_up_to);
+                if chunk_tx.send(text).is_err() {
+                    break;
+                }
+            }
+
+            batch.clear();
+            batch
+                .add(token, position, &[0], true)
+                .map_err(|e| format!("Failed to queue token: {}", e))?;
+            position += 1;
+            ctx.decode(&mut batch)
+                .map_err(|e| format!("Failed to generate: {}", e))?;
+        }
+
+        Ok(())
+    }
+
+    // Use the model's own chat template; fall back to ChatML, which most
+    // small instruction-tuned GGUF models understand
+    fn build_prompt(model: &LlamaModel, messages: &[(String, String)]) -> String {
+        let templated = model.chat_template(None).ok().and_then(|template| {
+            let chat: Vec<LlamaChatMessage> = messages
+                .iter()
+                .filter_map(|(role, content)| {
+                    LlamaChatMessage::new(role.clone(), content.clone()).ok()
+                })
+                .collect();
+            model.apply_chat_template(&template, &chat, true).ok()
+        });
+
+        templated.unwrap_or_else(|| {
+            let mut prompt = String::new();
+            for (role, content) in messages {
+                prompt.push_str(&format!("<|im_start|>{}\n{}<|im_end|>\n", role, content));
+            }
+            prompt.push_str("<|im_start|>assistant\n");
+            prompt
+        })
+    }
+}
+
+#[tauri::command]
+pub fn get_embedded_llm_config(app: AppHandle) -> Result<EmbeddedLlmConfig, String> {
+    load_config(&app)
+}
+
+#[tauri::command]
+pub fn update_embedded_llm_config(app: AppHandle, config: EmbeddedLlmConfig) -> Result<(), String> {
+    if !(512..=131_072).contains(&config.context_length) {
+        return Err("Invalid context_length: must be 512-131072".to_string());
+    }
+    if config.threads == 0 || config.threads > 64 {
+        return Err("Invalid threads: must be 1-64".to_string());
+    }
+    if config.max_tokens == 0 || config.max_tokens >= config.context_length {
+        return Err("Invalid max_tokens: must be between 1 and context_length".to_string());
+    }
+    if !(0.0..=2.0).contains(&config.temperature) {
+        return Err("Invalid temperature: must be 0.0-2.0".to_string());
+    }
+    if config.enabled {
+        let model_file = config
+            .model_file
+            .as_ref()
+            .ok_or("Import and select a model before enabling on-device inference")?;
+        if !is_plain_file_name(model_file) {
+            return Err("Invalid model file name".to_string());
+        }
+        if !get_models_dir(&app)?.join(model_file).exists() {
+            return Err(format!("On-device model not found: {}", model_file));
+        }
+    }
+
+    save_config(&app, config)
+}
+
+#[tauri::command]
+pub fn list_embedded_models(app: AppHandle) -> Result<Vec<EmbeddedModelInfo>, String> {
+    let models_dir = get_models_dir(&app)?;
+    let entries =
+        fs::read_dir(&models_dir).map_err(|e| format!("Failed to read models directory: {}", e))?;
+
+    let mut models: Vec<EmbeddedModelInfo> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "gguf"))
+        .filter_map(|entry| {
+            Some(EmbeddedModelInfo {
+                file_name: entry.file_name().to_str()?.to_string(),
+                size_bytes: entry.metadata().ok()?.len(),
+            })
+        })
+        .collect();
+    models.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+
+    Ok(models)
+}
+
+// Copies a GGUF file into the app's models directory (multi-GB, so off the async runtime).
+// An existing model with the same name is only overwritten when `replace` is set.
+#[tauri::command]
+pub async fn import_embedded_model(
+    app: AppHandle,
+    source_path: String,
+    replace: Option<bool>,
+) -> Result<EmbeddedModelInfo, String> {
+    let source = PathBuf::from(&source_path);
+    if !is_gguf(&source) {
+        return Err("Not a GGUF model file".to_string());
+    }
+
+    let mut file_name = source
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or("Invalid model file name")?
+        .to_string();
+    // list_embedded_models only shows .gguf files
+    if Path::new(&file_name).extension().and_then(|ext| ext.to_str()) != Some("gguf") {
+        file_name.push_str(".gguf");
+    }
+    let destination = get_models_dir(&app)?.join(&file_name);
+
+    let replacing = destination.exists();
+    if replacing && !replace.unwrap_or(false) {
+        return Err(format!("A model named {} already exists", file_name));
+    }
+    #[cfg(feature = "local-llm")]
+    if replacing {
+        app.state::<EmbeddedLlmState>().runtime.unload(&destination);
+    }
+
+    // Copy next to the destination, then rename over it: a generation still
+    // reading the old file keeps it intact, and a failed copy leaves nothing behind
+    let partial = destination.with_extension("gguf.part");
+    let size_bytes = tokio::task::spawn_blocking(move || {
+        let copied = fs::copy(&source, &partial).and_then(|size_bytes| {
+            fs::rename(&partial, &destination)?;
+            Ok(size_bytes)
+        });
+        if copied.is_err() {
+            let _ = fs::remove_file(&partial);
+        }
+        copied
+    })
+    .await
+    .map_err(|e| format!("Failed to import model: {}", e))?
+    .map_err(|e| format!("Failed to import model: {}", e))?;
+
+    Ok(EmbeddedModelInfo {
+        file_name,
+        size_bytes,
+    })
+}
+
+#[tauri::command]
+pub fn delete_embedded_model(app: AppHandle, file_name: String) -> Result<(), String> {
+    if !is_plain_file_name(&file_name) {
+        return Err("Invalid model file name".to_string());
+    }
+
+    let mut config = load_config(&app)?;
+    if config.model_file.as_deref() == Some(file_name.as_str()) {
+        config.model_file = None;
+        config.enabled = false;
+        save_config(&app, config)?;
+    }
+
+    let path = get_models_dir(&app)?.join(&file_name);
+    fs::remove_file(&path).map_err(|e| format!("Failed to delete model: {}", e))
+}
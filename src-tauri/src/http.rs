@@ -0,0 +1,497 @@
+// Shared HTTP layer for every outbound call: pooled clients, per-endpoint
+// timeouts, retries with exponential backoff + jitter, and a per-provider
+// circuit breaker that stops hammering a provider that keeps failing.
+use rand::Rng;
+use reqwest::{Client, RequestBuilder, Response, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+use tracing::warn;
+
+use crate::config_store::{JsonConfig, JsonConfigStore};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndpointKind {
+    // APP_ENDPOINT: /api/response, /api/models, /api/prompt, /api/activity (GET)
+    AppApi,
+    // PAYMENT_ENDPOINT: activate/deactivate/validate/checkout
+    License,
+    Chat,
+    Transcription,
+    // Fire-and-forget activity and error reporting
+    Telemetry,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EndpointPolicy {
+    pub connect_timeout_ms: u64,
+    // Max idle time between reads, so long chat streams aren't cut off
+    pub read_timeout_ms: u64,
+    pub max_retries: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+}
+
+impl EndpointPolicy {
+    fn new(connect_timeout_ms: u64, read_timeout_ms: u64, max_retries: u32) -> Self {
+        Self {
+            connect_timeout_ms,
+            read_timeout_ms,
+            max_retries,
+            base_delay_ms: 300,
+            max_delay_ms: 5000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpConfig {
+    pub app_api: EndpointPolicy,
+    pub license: EndpointPolicy,
+    pub chat: EndpointPolicy,
+    pub transcription: EndpointPolicy,
+    pub telemetry: EndpointPolicy,
+    pub breaker_failure_threshold: u32,
+    pub breaker_cooldown_secs: u64,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            app_api: EndpointPolicy::new(5_000, 15_000, 2),
+            license: EndpointPolicy::new(5_000, 20_000, 2),
+            chat: EndpointPolicy::new(10_000, 60_000, 2),
+            transcription: EndpointPolicy::new(10_000, 60_000, 2),
+            telemetry: EndpointPolicy::new(5_000, 10_000, 0),
+            breaker_failure_threshold: 5,
+            breaker_cooldown_secs: 30,
+        }
+    }
+}
+
+impl JsonConfig for HttpConfig {
+    const FILE_NAME: &'static str = "http.json";
+    const LABEL: &'static str = "HTTP";
+}
+
+impl HttpConfig {
+    fn policy(&self, kind: EndpointKind) -> &EndpointPolicy {
+        match kind {
+            EndpointKind::AppApi => &self.app_api,
+            EndpointKind::License => &self.license,
+            EndpointKind::Chat => &self.chat,
+            EndpointKind::Transcription => &self.transcription,
+            EndpointKind::Telemetry => &self.telemetry,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderStatus {
+    pub provider: String,
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    pub retry_after_ms: u64,
+}
+
+#[derive(Debug, Default)]
+struct Breaker {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    trial_in_flight: bool,
+}
+
+#[derive(Default)]
+pub struct HttpState {
+    config: JsonConfigStore<HttpConfig>,
+    // One pooled client per (connect, read) timeout pair
+    clients: Mutex<HashMap<(u64, u64), Client>>,
+    breakers: Mutex<HashMap<String, Breaker>>,
+}
+
+// The one request let through a half-open breaker. Dropping it clears the
+// in-flight flag; if the request finished, record_success/record_failure have
+// already cleared it and this is a no-op.
+struct BreakerProbe<'a> {
+    breakers: &'a Mutex<HashMap<String, Breaker>>,
+    provider: String,
+    consecutive_failures: u32,
+}
+
+impl Drop for BreakerProbe<'_> {
+    fn drop(&mut self) {
+        let mut breakers = match self.breakers.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if let Some(breaker) = breakers.get_mut(&self.provider) {
+            breaker.trial_in_flight = false;
+        }
+    }
+}
+
+impl HttpState {
+    fn check_breaker(
+        &self,
+        config: &HttpConfig,
+        provider: &str,
+    ) -> Result<Option<BreakerProbe<'_>>, HttpError> {
+        let mut breakers = match self.breakers.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let breaker = breakers.entry(provider.to_string()).or_default();
+
+        let Some(opened_at) = breaker.opened_at else {
+            return Ok(None);
+        };
+
+        let cooldown = Duration::from_secs(config.breaker_cooldown_secs);
+        let elapsed = opened_at.elapsed();
+        if elapsed < cooldown || breaker.trial_in_flight {
+            return Err(HttpError::CircuitOpen {
+                provider: provider.to_string(),
+                retry_after: cooldown.saturating_sub(elapsed),
+            });
+        }
+
+        // Cooldown over: let exactly one request through to probe the provider
+        breaker.trial_in_flight = true;
+        Ok(Some(BreakerProbe {
+            breakers: &self.breakers,
+            provider: provider.to_string(),
+            consecutive_failures: breaker.consecutive_failures,
+        }))
+    }
+}
+
+#[derive(Debug)]
+pub enum HttpError {
+    Build(String),
+    Request(reqwest::Error),
+    CircuitOpen { provider: String, retry_after: Duration },
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpError::Build(message) => write!(f, "{}", message),
+            HttpError::Request(e) => write!(f, "{}", e),
+            HttpError::CircuitOpen {
+                provider,
+                retry_after,
+            } => write!(
+                f,
+                "{} is temporarily unavailable after repeated failures, retrying in {}s",
+                provider,
+                retry_after.as_secs().max(1)
+            ),
+        }
+    }
+}
+
+/// Breaker key for a URL: its host, so primary and fallback endpoints on
+/// different providers trip independently.
+pub fn provider_key(url: &str) -> String {
+    Url::parse(url)
+        .ok()
+        .and_then(|parsed| parsed.host_str().map(|host| host.to_string()))
+        .unwrap_or_else(|| url.to_string())
+}
+
+/// Send a request through the shared layer. `build` is called once per attempt
+/// (multipart bodies can't be cloned). Non-success responses that aren't worth
+/// retrying are returned as-is so callers keep their own error mapping.
+pub async fn send(
+    app: &AppHandle,
+    kind: EndpointKind,
+    provider: &str,
+    build: impl Fn(&Client) -> Result<RequestBuilder, String>,
+) -> Result<Response, HttpError> {
+    let state = app.state::<HttpState>();
+    let config = state.config.load_or_default(app);
+    let policy = config.policy(kind).clone();
+    let client = shared_client(&state, &policy)?;
+
+    // Held until this call returns or is dropped mid-flight (cancelled chat,
+    // losing comparison run), so an abandoned probe can't wedge the breaker
+    let probe = state.check_breaker(&config, provider)?;
+    if let Some(probe) = &probe {
+        emit_status(
+            app,
+            provider,
+            probe.consecutive_failures,
+            CircuitState::HalfOpen,
+            Duration::ZERO,
+        );
+    }
+
+    let mut attempt = 0;
+    loop {
+        let request = build(&client).map_err(HttpError::Build)?;
+        let retries_left = attempt < policy.max_retries;
+
+        match request.send().await {
+            Ok(response) if is_retryable_status(response.status()) => {
+                if !retries_left || kind == EndpointKind::License {
+                    record_failure(app, &state, &config, provider);
+                    return Ok(response);
+                }
+                let delay = retry_after(&response).unwrap_or_else(|| backoff_delay(&policy, attempt));
+                warn!(
+                    "{} returned {}, retrying in {}ms",
+                    provider,
+                    response.status(),
+                    delay.as_millis()
+                );
+                tokio::time::sleep(delay).await;
+            }
+            Ok(response) => {
+                record_success(app, &state, provider);
+                return Ok(response);
+            }
+            Err(e) => {
+                // License calls aren't idempotent: only retry if the request never left
+                let may_retry = retries_left && (kind != EndpointKind::License || e.is_connect());
+                if !may_retry {
+                    record_failure(app, &state, &config, provider);
+                    return Err(HttpError::Request(e));
+                }
+                let delay = backoff_delay(&policy, attempt);
+                warn!("Request to {} failed, retrying in {}ms: {}", provider, delay.as_millis(), e);
+                tokio::time::sleep(delay).await;
+            }
+        }
+
+        attempt += 1;
+    }
+}
+
+fn shared_client(state: &HttpState, policy: &EndpointPolicy) -> Result<Client, HttpError> {
+    let mut clients = match state.clients.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    let key = (policy.connect_timeout_ms, policy.read_timeout_ms);
+    if let Some(client) = clients.get(&key) {
+        return Ok(client.clone());
+    }
+
+    let client = Client::builder()
+        .connect_timeout(Duration::from_millis(policy.connect_timeout_ms))
+        .read_timeout(Duration::from_millis(policy.read_timeout_ms))
+        .pool_idle_timeout(Duration::from_secs(90))
+        .build()
+        .map_err(|e| HttpError::Build(format!("Failed to create HTTP client: {}", e)))?;
+    clients.insert(key, client.clone());
+    Ok(client)
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(status.as_u16(), 408 | 429 | 500 | 502 | 503 | 504)
+}
+
+// Full jitter: uniform in [0, min(max, base * 2^attempt)]
+fn backoff_delay(policy: &EndpointPolicy, attempt: u32) -> Duration {
+    let ceiling = policy
+        .base_delay_ms
+        .saturating_mul(1u64 << attempt.min(16))
+        .min(policy.max_delay_ms);
+    Duration::from_millis(rand::thread_rng().gen_range(0..=ceiling))
+}
+
+fn retry_after(response: &Response) -> Option<Duration> {
+    let seconds = response
+        .headers()
+        .get("retry-after")?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    // Don't let a provider park us for minutes
+    Some(Duration::from_secs(seconds.min(10)))
+}
+
+fn record_success(app: &AppHandle, state: &HttpState, provider: &str) {
+    let mut breakers = match state.breakers.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let Some(breaker) = breakers.get_mut(provider) else {
+        return;
+    };
+
+    let was_open = breaker.opened_at.is_some();
+    *breaker = Breaker::default();
+    if was_open {
+        emit_status(app, provider, 0, CircuitState::Closed, Duration::ZERO);
+    }
+}
+
+fn record_failure(app: &AppHandle, state: &HttpState, config: &HttpConfig, provider: &str) {
+    let mut breakers = match state.breakers.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let breaker = breakers.entry(provider.to_string()).or_default();
+
+    breaker.consecutive_failures += 1;
+    let failed_trial = breaker.trial_in_flight;
+    breaker.trial_in_flight = false;
+
+    if failed_trial || breaker.consecutive_failures >= config.breaker_failure_threshold {
+        breaker.opened_at = Some(Instant::now());
+        let cooldown = Duration::from_secs(config.breaker_cooldown_secs);
+        emit_status(
+            app,
+            provider,
+            breaker.consecutive_failures,
+            CircuitState::Open,
+            cooldown,
+        );
+    }
+}
+
+fn emit_status(
+    app: &AppHandle,
+    provider: &str,
+    consecutive_failures: u32,
+    state: CircuitState,
+    retry_after: Duration,
+) {
+    let _ = app.emit(
+        "provider-status",
+        ProviderStatus {
+            provider: provider.to_string(),
+            state,
+            consecutive_failures,
+            retry_after_ms: retry_after.as_millis() as u64,
+        },
+    );
+}
+
+#[tauri::command]
+pub async fn get_http_config(app: AppHandle) -> Result<HttpConfig, String> {
+    app.state::<HttpState>().config.load(&app)
+}
+
+#[tauri::command]
+pub async fn update_http_config(app: AppHandle, config: HttpConfig) -> Result<(), String> {
+    for policy in [
+        &config.app_api,
+        &config.license,
+        &config.chat,
+        &config.transcription,
+        &config.telemetry,
+    ] {
+        if policy.connect_timeout_ms == 0 || policy.read_timeout_ms == 0 {
+            return Err("Timeouts must be greater than 0".to_string());
+        }
+        if policy.max_retries > 5 {
+            return Err("Invalid max_retries: must be <= 5".to_string());
+        }
+    }
+    if config.breaker_failure_threshold == 0 {
+        return Err("Invalid breaker_failure_threshold: must be at least 1".to_string());
+    }
+
+    app.state::<HttpState>().config.save(&app, config)
+}
+
+#[tauri::command]
+pub async fn get_provider_status(app: AppHandle) -> Result<Vec<ProviderStatus>, String> {
+    let state = app.state::<HttpState>();
+    let cooldown = Duration::from_secs(state.config.load(&app)?.breaker_cooldown_secs);
+    let breakers = state
+        .breakers
+        .lock()
+        .map_err(|e| format!("Failed to get provider status: {}", e))?;
+
+    Ok(breakers
+        .iter()
+        .map(|(provider, breaker)| {
+            let (state, retry_after) = match breaker.opened_at {
+                None => (CircuitState::Closed, Duration::ZERO),
+                Some(_) if breaker.trial_in_flight => (CircuitState::HalfOpen, Duration::ZERO),
+                Some(opened_at) => (CircuitState::Open, cooldown.saturating_sub(opened_at.elapsed())),
+            };
+            ProviderStatus {
+                provider: provider.clone(),
+                state,
+                consecutive_failures: breaker.consecutive_failures,
+                retry_after_ms: retry_after.as_millis() as u64,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROVIDER: &str = "api.example.com";
+
+    fn open_breaker(state: &HttpState) {
+        state.breakers.lock().unwrap().insert(
+            PROVIDER.to_string(),
+            Breaker {
+                consecutive_failures: 5,
+                opened_at: Some(Instant::now()),
+                trial_in_flight: false,
+            },
+        );
+    }
+
+    fn cooled_down() -> HttpConfig {
+        HttpConfig {
+            breaker_cooldown_secs: 0,
+            ..HttpConfig::default()
+        }
+    }
+
+    #[test]
+    fn half_open_breaker_admits_one_probe() {
+        let state = HttpState::default();
+        let config = cooled_down();
+        open_breaker(&state);
+
+        let probe = state.check_breaker(&config, PROVIDER).unwrap();
+        assert!(probe.is_some());
+        assert!(matches!(
+            state.check_breaker(&config, PROVIDER),
+            Err(HttpError::CircuitOpen { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn dropped_probe_reopens_for_next_call() {
+        let state = HttpState::default();
+        let config = cooled_down();
+        open_breaker(&state);
+
+        // Same shape as a send() cancelled while its request is in flight
+        let request = async {
+            let _probe = state.check_breaker(&config, PROVIDER).unwrap();
+            std::future::pending::<()>().await;
+        };
+        let cancelled = tokio::time::timeout(Duration::from_millis(20), request).await;
+        assert!(cancelled.is_err());
+
+        assert!(!state.breakers.lock().unwrap()[PROVIDER].trial_in_flight);
+        let next = state.check_breaker(&config, PROVIDER).unwrap();
+        assert!(next.is_some());
+    }
+}
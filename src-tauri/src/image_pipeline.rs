@@ -0,0 +1,267 @@
+// Prepares screenshots and pasted images for upload. Captures are
+// full-resolution PNGs, which on 4K/5K displays run to several megabytes;
+// models downscale them server-side anyway, so we shrink and re-encode first.
+use base64::{engine::general_purpose, Engine as _};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, ImageEncoder, ImageFormat};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::chat_adapters::ChatImage;
+use crate::config_store::{JsonConfig, JsonConfigStore};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Jpeg,
+    // Lossless (the image crate has no lossy WebP encoder), so `quality`
+    // must stay at its default
+    Webp,
+}
+
+const DEFAULT_QUALITY: u8 = 80;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImagePipelineConfig {
+    pub enabled: bool,
+    pub max_edge: u32,
+    pub format: OutputFormat,
+    pub quality: u8,
+}
+
+impl Default for ImagePipelineConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_edge: 1920,             // Above what vision models look at in detail
+            format: OutputFormat::Jpeg, // Screenshots compress ~10x vs PNG
+            quality: DEFAULT_QUALITY,   // JPEG only
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PreparedImageInfo {
+    pub request_id: String,
+    pub index: usize,
+    pub mime: String,
+    pub width: u32,
+    pub height: u32,
+    pub original_bytes: usize,
+    pub bytes: usize,
+}
+
+impl JsonConfig for ImagePipelineConfig {
+    const FILE_NAME: &'static str = "image_pipeline.json";
+    const LABEL: &'static str = "image pipeline";
+}
+
+#[derive(Default)]
+pub struct ImagePipelineState {
+    config: JsonConfigStore<ImagePipelineConfig>,
+}
+
+fn load_config(app: &AppHandle) -> Result<ImagePipelineConfig, String> {
+    app.state::<ImagePipelineState>().config.load(app)
+}
+
+/// MIME type from the image's magic bytes; JPEG if they aren't recognised.
+/// Accepts raw base64 or a data URL.
+pub fn detect_mime(data_base64: &str) -> &'static str {
+    // 64 base64 chars decode to 48 bytes, plenty for every signature
+    let prefix: String = strip_data_url(data_base64.trim()).chars().take(64).collect();
+    general_purpose::STANDARD
+        .decode(prefix.as_bytes())
+        .ok()
+        .and_then(|bytes| image::guess_format(&bytes).ok())
+        .map(|format| format.to_mime_type())
+        .unwrap_or("image/jpeg")
+}
+
+// Accepts raw base64 or a data URL
+fn strip_data_url(data: &str) -> &str {
+    match data.split_once(";base64,") {
+        Some((meta, rest)) if meta.starts_with("data:") => rest,
+        _ => data,
+    }
+}
+
+/// Downscale and re-encode one base64 image according to `config`, returning
+/// it with its final dimensions. Images that are already small and in the
+/// target format are left untouched.
+pub fn prepare(
+    data_base64: &str,
+    config: &ImagePipelineConfig,
+) -> Result<(ChatImage, (u32, u32)), String> {
+    let data_base64 = strip_data_url(data_base64.trim());
+    let original = general_purpose::STANDARD
+        .decode(data_base64)
+        .map_err(|e| format!("Invalid base64 image: {}", e))?;
+    let source_format =
+        image::guess_format(&original).map_err(|e| format!("Unrecognised image format: {}", e))?;
+    let passthrough = |dimensions: (u32, u32)| {
+        let image = ChatImage {
+            mime: source_format.to_mime_type().to_string(),
+            data: data_base64.to_string(),
+        };
+        Ok((image, dimensions))
+    };
+
+    if !config.enabled {
+        // Reads the header only
+        let dimensions = image::ImageReader::with_format(Cursor::new(&original), source_format)
+            .into_dimensions()
+            .unwrap_or((0, 0));
+        return passthrough(dimensions);
+    }
+
+    let decoded = image::load_from_memory_with_format(&original, source_format)
+        .map_err(|e| format!("Failed to decode image: {}", e))?;
+    let needs_resize = decoded.width().max(decoded.height()) > config.max_edge;
+    let target_format = match config.format {
+        OutputFormat::Jpeg => ImageFormat::Jpeg,
+        OutputFormat::Webp => ImageFormat::WebP,
+    };
+    if !needs_resize && source_format == target_format {
+        return passthrough(decoded.dimensions());
+    }
+
+    let resized = if needs_resize {
+        // Keeps the aspect ratio; the longer edge becomes max_edge
+        decoded.resize(config.max_edge, config.max_edge, FilterType::CatmullRom)
+    } else {
+        decoded
+    };
+    let encoded = encode(&resized, config)?;
+
+    // Re-encoding a small, already-compressed image can make it bigger
+    if !needs_resize && encoded.len() >= original.len() {
+        return passthrough(resized.dimensions());
+    }
+
+    let image = ChatImage {
+        mime: target_format.to_mime_type().to_string(),
+        data: general_purpose::STANDARD.encode(encoded),
+    };
+    Ok((image, resized.dimensions()))
+}
+
+fn encode(image: &DynamicImage, config: &ImagePipelineConfig) -> Result<Vec<u8>, String> {
+    let mut buffer = Cursor::new(Vec::new());
+    match config.format {
+        OutputFormat::Jpeg => {
+            // JPEG has no alpha channel
+            let rgb = image.to_rgb8();
+            JpegEncoder::new_with_quality(&mut buffer, config.quality)
+                .write_image(rgb.as_raw(), rgb.width(), rgb.height(), image::ExtendedColorType::Rgb8)
+                .map_err(|e| format!("Failed to encode JPEG: {}", e))?;
+        }
+        OutputFormat::Webp => {
+            let rgba = image.to_rgba8();
+            WebPEncoder::new_lossless(&mut buffer)
+                .write_image(rgba.as_raw(), rgba.width(), rgba.height(), image::ExtendedColorType::Rgba8)
+                .map_err(|e| format!("Failed to encode WebP: {}", e))?;
+        }
+    }
+    Ok(buffer.into_inner())
+}
+
+// The image as received, labelled by its magic bytes
+fn unprocessed(data: &str) -> ChatImage {
+    ChatImage {
+        mime: detect_mime(data).to_string(),
+        data: data.to_string(),
+    }
+}
+
+/// Prepare every image of a chat request off the async runtime, emitting a
+/// `chat_image_prepared` event with the upload size of each. An image that
+/// can't be processed is sent as-is rather than failing the request.
+pub async fn prepare_images(app: &AppHandle, request_id: &str, images: Vec<String>) -> Vec<ChatImage> {
+    if images.is_empty() {
+        return Vec::new();
+    }
+
+    let config = app.state::<ImagePipelineState>().config.load_or_default(app);
+
+    // Shared with the blocking task so the originals survive if it dies
+    let images = Arc::new(images);
+    let prepared = tauri::async_runtime::spawn_blocking({
+        let images = images.clone();
+        move || {
+            images
+                .iter()
+                .map(|data| {
+                    let data = strip_data_url(data.trim());
+                    let original_bytes = data.len() * 3 / 4;
+                    let (image, dimensions) = prepare(data, &config).unwrap_or_else(|e| {
+                        tracing::warn!("Sending image unprocessed: {}", e);
+                        (unprocessed(data), (0, 0))
+                    });
+                    (image, dimensions, original_bytes)
+                })
+                .collect::<Vec<_>>()
+        }
+    })
+    .await
+    .unwrap_or_else(|e| {
+        tracing::warn!("Image processing failed, sending images unprocessed: {}", e);
+        images
+            .iter()
+            .map(|data| {
+                let data = strip_data_url(data.trim());
+                (unprocessed(data), (0, 0), data.len() * 3 / 4)
+            })
+            .collect()
+    });
+
+    prepared
+        .into_iter()
+        .enumerate()
+        .map(|(index, (image, (width, height), original_bytes))| {
+            let _ = app.emit(
+                "chat_image_prepared",
+                PreparedImageInfo {
+                    request_id: request_id.to_string(),
+                    index,
+                    mime: image.mime.clone(),
+                    width,
+                    height,
+                    original_bytes,
+                    bytes: image.data.len() * 3 / 4,
+                },
+            );
+            image
+        })
+        .collect()
+}
+
+#[tauri::command]
+pub fn get_image_pipeline_config(app: AppHandle) -> Result<ImagePipelineConfig, String> {
+    load_config(&app)
+}
+
+#[tauri::command]
+pub fn update_image_pipeline_config(
+    app: AppHandle,
+    config: ImagePipelineConfig,
+) -> Result<(), String> {
+    if !(256..=8192).contains(&config.max_edge) {
+        return Err("Invalid max_edge: must be between 256 and 8192".to_string());
+    }
+    if !(1..=100).contains(&config.quality) {
+        return Err("Invalid quality: must be between 1 and 100".to_string());
+    }
+    if config.format == OutputFormat::Webp && config.quality != DEFAULT_QUALITY {
+        return Err(format!(
+            "Invalid quality: must be {} for webp, which is always encoded lossless",
+            DEFAULT_QUALITY
+        ));
+    }
+
+    app.state::<ImagePipelineState>().config.save(&app, config)
+}
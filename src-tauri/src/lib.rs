@@ -1,9 +1,29 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 mod activate;
 mod api;
+mod api_config_cache;
 mod capture;
+mod chat_adapters;
+mod chat_fallback;
+mod chat_tools;
+mod config_store;
+mod context_window;
 mod db;
+mod direct_provider;
+mod embedded_llm;
+mod http;
+mod image_pipeline;
+mod prompt_templates;
 mod shortcuts;
+mod sse;
+mod structured_output;
+mod stt_providers;
+mod stt_stream;
+mod transcript_log;
+mod transcription_queue;
+mod usage_budget;
+mod usage_ledger;
+mod vocabulary;
 mod window;
 use std::sync::{Arc, Mutex};
 use tauri::{AppHandle, Manager, WebviewWindow};
@@ -44,6 +64,23 @@ pub fn run() {
         .manage(AudioState::default())
         .manage(MicState::default())
         .manage(CaptureState::default())
+        .manage(image_pipeline::ImagePipelineState::default())
+        .manage(http::HttpState::default())
+        .manage(stt_stream::SttStreamState::default())
+        .manage(transcript_log::TranscriptLogState::default())
+        .manage(api::TranscriptFilterState::default())
+        .manage(api::ChatStreamState::default())
+        .manage(api_config_cache::ApiConfigCacheState::default())
+        .manage(direct_provider::DirectProviderState::default())
+        .manage(chat_fallback::ChatFallbackState::default())
+        .manage(context_window::ContextWindowState::default())
+        .manage(usage_ledger::UsageLedgerState::default())
+        .manage(usage_budget::UsageBudgetState::default())
+        .manage(embedded_llm::EmbeddedLlmState::default())
+        .manage(vocabulary::VocabularyState::default())
+        .manage(prompt_templates::PromptTemplateState::default())
+        .manage(stt_providers::SttProviderState::default())
+        .manage(transcription_queue::TranscriptionQueueState::default())
         .manage(shortcuts::WindowVisibility {
             is_hidden: Mutex::new(false),
         })
@@ -84,6 +121,8 @@ pub fn run() {
             capture::start_screen_capture,
             capture::capture_selected_area,
             capture::close_overlay_window,
+            image_pipeline::get_image_pipeline_config,
+            image_pipeline::update_image_pipeline_config,
             shortcuts::check_shortcuts_registered,
             shortcuts::get_registered_shortcuts,
             shortcuts::update_shortcuts,
@@ -101,11 +140,39 @@ pub fn run() {
             activate::secure_storage_get,
             activate::secure_storage_remove,
             api::transcribe_audio,
+            api::transcribe_audio_detailed,
+            api::get_transcript_filter_config,
+            api::update_transcript_filter_config,
             api::chat_stream_response,
+            api::cancel_chat_stream,
+            api::chat_compare,
+            direct_provider::get_direct_provider_config,
+            direct_provider::save_direct_provider_config,
+            direct_provider::list_local_models,
+            chat_fallback::get_chat_fallback_config,
+            chat_fallback::update_chat_fallback_config,
+            context_window::get_context_config,
+            context_window::update_context_config,
+            context_window::estimate_chat_context,
+            usage_ledger::get_daily_usage,
+            usage_ledger::get_monthly_usage,
+            usage_ledger::get_usage_pricing,
+            usage_ledger::update_usage_pricing,
+            usage_budget::get_usage_budget_config,
+            usage_budget::update_usage_budget_config,
+            usage_budget::get_usage_budget_status,
+            embedded_llm::get_embedded_llm_config,
+            embedded_llm::update_embedded_llm_config,
+            embedded_llm::list_embedded_models,
+            embedded_llm::import_embedded_model,
+            embedded_llm::delete_embedded_model,
             api::fetch_models,
             api::create_system_prompt,
             api::check_license_status,
             api::get_activity,
+            api::refresh_api_response_config,
+            api_config_cache::get_api_config_cache_settings,
+            api_config_cache::update_api_config_cache_settings,
             speaker::start_system_audio_capture,
             speaker::stop_system_audio_capture,
             speaker::manual_stop_continuous,
@@ -119,6 +186,29 @@ pub fn run() {
             mic::stop_mic_capture,
             mic::is_mic_capturing,
             mic::list_mic_devices,
+            stt_stream::start_transcript_stream,
+            stt_stream::stop_transcript_stream,
+            stt_stream::is_transcript_streaming,
+            vocabulary::get_vocabulary_profiles,
+            vocabulary::save_vocabulary_profile,
+            vocabulary::delete_vocabulary_profile,
+            vocabulary::set_active_vocabulary_profile,
+            vocabulary::preview_vocabulary_rules,
+            prompt_templates::get_prompt_template_config,
+            prompt_templates::update_prompt_template_config,
+            prompt_templates::check_prompt_template,
+            prompt_templates::preview_prompt_template,
+            stt_providers::get_stt_providers,
+            stt_providers::save_stt_provider,
+            stt_providers::delete_stt_provider,
+            stt_providers::set_active_stt_provider,
+            stt_providers::transcribe_with_stt_provider,
+            transcription_queue::get_transcription_queue_config,
+            transcription_queue::update_transcription_queue_config,
+            transcription_queue::get_transcription_queue_status,
+            http::get_http_config,
+            http::update_http_config,
+            http::get_provider_status,
         ])
         .setup(|app| {
             // Setup main window positioning
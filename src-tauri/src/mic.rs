@@ -9,6 +9,9 @@ use std::sync::{Arc, Mutex};
 use tauri::{AppHandle, Emitter, Manager};
 use tracing::error;
 
+use crate::stt_stream::{push_frame, StreamSource};
+use crate::transcription_queue::enqueue_segment;
+
 /// State for mic capture — only contains Send+Sync types.
 /// The cpal::Stream lives on a dedicated thread (not stored here).
 pub struct MicState {
@@ -349,6 +352,7 @@ fn build_input_stream<T: cpal::Sample + cpal::SizedSample + Send + 'static>(
 where
     f32: cpal::FromSample<T>,
 {
+    let sample_rate = config.sample_rate.0;
     let stream = device
         .build_input_stream(
             config,
@@ -374,11 +378,16 @@ where
                         .collect()
                 };
 
+                // Feed the streaming transcriber (no-op unless a session is open)
+                push_frame(&app, StreamSource::Mic, sample_rate, &mono);
+
                 // Feed to VAD
                 if let Ok(mut vad) = vad_state.lock() {
                     let segments = vad.feed(&mono);
                     for b64 in segments {
-                        let _ = app.emit("mic-speech-detected", &b64);
+                        if !enqueue_segment(&app, StreamSource::Mic, &b64) {
+                            let _ = app.emit("mic-speech-detected", &b64);
+                        }
                     }
                 }
             },
@@ -0,0 +1,200 @@
+// `{{name}}` placeholders in system prompts, filled in right before a chat
+// request is built. Prompts in the system_prompts table stay as written; only
+// the copy sent to the model is rendered, and only the variables a prompt
+// uses are read (the clipboard isn't touched unless asked for).
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+use crate::config_store::{JsonConfig, JsonConfigStore};
+use crate::transcript_log;
+
+const BUILTIN_VARIABLES: [&str; 4] = ["date", "clipboard", "transcript_last_5m", "resume"];
+const TRANSCRIPT_WINDOW: Duration = Duration::from_secs(5 * 60);
+// A copied log file shouldn't crowd the conversation out of the context window
+const MAX_CLIPBOARD_CHARS: usize = 20_000;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PromptTemplateConfig {
+    // Value of {{resume}}
+    pub resume: String,
+    // User-defined {{name}} values
+    pub variables: BTreeMap<String, String>,
+}
+
+impl JsonConfig for PromptTemplateConfig {
+    const FILE_NAME: &'static str = "prompt_templates.json";
+    const LABEL: &'static str = "prompt template";
+}
+
+#[derive(Default)]
+pub struct PromptTemplateState {
+    config: JsonConfigStore<PromptTemplateConfig>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PromptTemplateCheck {
+    // Every variable the template uses, in order of first use
+    pub variables: Vec<String>,
+    // Variables that are neither built in nor user-defined
+    pub unknown: Vec<String>,
+}
+
+fn load_config(app: &AppHandle) -> Result<PromptTemplateConfig, String> {
+    app.state::<PromptTemplateState>().config.load(app)
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+// Byte ranges and names of the placeholders, in order. Braces around anything
+// other than a plain name (`{{ user.name }}`, `{{}}`) are left as text.
+fn placeholders(template: &str) -> Vec<(Range<usize>, &str)> {
+    let mut found = Vec::new();
+    let mut from = 0;
+    while let Some(open) = template[from..].find("{{") {
+        let start = from + open;
+        let Some(close) = template[start + 2..].find("}}") else {
+            break;
+        };
+        let end = start + 2 + close + 2;
+        let name = template[start + 2..end - 2].trim();
+        if !name.is_empty() && name.chars().all(is_name_char) {
+            found.push((start..end, name));
+            from = end;
+        } else {
+            from = start + 1;
+        }
+    }
+    found
+}
+
+fn check(config: &PromptTemplateConfig, template: &str) -> PromptTemplateCheck {
+    let mut variables: Vec<String> = Vec::new();
+    for (_, name) in placeholders(template) {
+        if !variables.iter().any(|seen| seen == name) {
+            variables.push(name.to_string());
+        }
+    }
+    let unknown = variables
+        .iter()
+        .filter(|name| {
+            !BUILTIN_VARIABLES.contains(&name.as_str()) && !config.variables.contains_key(*name)
+        })
+        .cloned()
+        .collect();
+
+    PromptTemplateCheck { variables, unknown }
+}
+
+fn read_clipboard() -> String {
+    let text = arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .unwrap_or_else(|e| {
+            // Empty or non-text clipboards land here too
+            tracing::debug!("Clipboard has no text: {}", e);
+            String::new()
+        });
+    text.chars().take(MAX_CLIPBOARD_CHARS).collect()
+}
+
+fn resolve(app: &AppHandle, config: &PromptTemplateConfig, name: &str) -> String {
+    match name {
+        "date" => chrono::Local::now().format("%A, %B %-d, %Y").to_string(),
+        "clipboard" => read_clipboard(),
+        "transcript_last_5m" => {
+            transcript_log::format_entries(&transcript_log::recent(app, TRANSCRIPT_WINDOW))
+        }
+        "resume" => config.resume.clone(),
+        _ => config.variables.get(name).cloned().unwrap_or_default(),
+    }
+}
+
+/// The template with every known placeholder filled in. Unknown ones are
+/// left as written: prompts saved before templating existed may contain
+/// literal `{{word}}` text, and the editor flags them via check/preview.
+pub fn render(app: &AppHandle, template: &str) -> Result<String, String> {
+    let found = placeholders(template);
+    if found.is_empty() {
+        return Ok(template.to_string());
+    }
+
+    let config = load_config(app)?;
+    let checked = check(&config, template);
+    let values: HashMap<&str, String> = checked
+        .variables
+        .iter()
+        .filter(|name| !checked.unknown.contains(name))
+        .map(|name| (name.as_str(), resolve(app, &config, name)))
+        .collect();
+    let mut rendered = String::with_capacity(template.len());
+    let mut last = 0;
+    for (range, name) in found {
+        let Some(value) = values.get(name) else {
+            continue;
+        };
+        rendered.push_str(&template[last..range.start]);
+        rendered.push_str(value);
+        last = range.end;
+    }
+    rendered.push_str(&template[last..]);
+    Ok(rendered)
+}
+
+#[tauri::command]
+pub fn get_prompt_template_config(app: AppHandle) -> Result<PromptTemplateConfig, String> {
+    load_config(&app)
+}
+
+#[tauri::command]
+pub fn update_prompt_template_config(
+    app: AppHandle,
+    config: PromptTemplateConfig,
+) -> Result<(), String> {
+    for name in config.variables.keys() {
+        if name.is_empty() || !name.chars().all(is_name_char) {
+            return Err(format!(
+                "Invalid variable name {:?}: must only contain letters, digits and _",
+                name
+            ));
+        }
+        if BUILTIN_VARIABLES.contains(&name.as_str()) {
+            return Err(format!(
+                "Invalid variable name {:?}: must not replace a built-in variable",
+                name
+            ));
+        }
+    }
+
+    app.state::<PromptTemplateState>().config.save(&app, config)
+}
+
+// For the prompt editor: which variables a template uses and which are unknown
+#[tauri::command]
+pub fn check_prompt_template(app: AppHandle, template: String) -> Result<PromptTemplateCheck, String> {
+    let config = load_config(&app)?;
+    Ok(check(&config, &template))
+}
+
+// The prompt as the model would receive it right now. Unknown variables are
+// an error here so typos show up in the editor.
+#[tauri::command]
+pub fn preview_prompt_template(app: AppHandle, template: String) -> Result<String, String> {
+    let checked = check(&load_config(&app)?, &template);
+    if !checked.unknown.is_empty() {
+        return Err(format!(
+            "Unknown prompt variables: {}",
+            checked
+                .unknown
+                .iter()
+                .map(|name| format!("{{{{{}}}}}", name))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+
+    render(&app, &template)
+}
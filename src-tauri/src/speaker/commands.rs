@@ -1,5 +1,7 @@
 // Pluely AI Speech Detection, and capture system audio (speaker output) as a stream of f32 samples.
 use crate::speaker::SpeakerInput;
+use crate::stt_stream::{push_frame, StreamSource};
+use crate::transcription_queue::enqueue_segment;
 use anyhow::Result;
 use base64::{engine::general_purpose::STANDARD as B64, Engine as _};
 use futures_util::StreamExt;
@@ -163,6 +165,9 @@ async fn run_vad_capture(
             // Apply noise gate BEFORE VAD (critical for accuracy)
             let mono = apply_noise_gate(&mono, config.noise_gate_threshold);
 
+            // Feed the streaming transcriber (no-op unless a session is open)
+            push_frame(&app, StreamSource::Speaker, sr, &mono);
+
             let (rms, peak) = calculate_audio_metrics(&mono);
             let is_speech = rms > config.sensitivity_rms || peak > config.peak_threshold;
 
@@ -187,7 +192,7 @@ async fn run_vad_capture(
                     let normalized_buffer = normalize_audio_level(&speech_buffer, 0.1);
                     if let Ok(b64) = samples_to_wav_b64(sr, &normalized_buffer) {
                         // let duration = speech_buffer.len() as f32 / sr as f32;
-                        let _ = app.emit("speech-detected", b64);
+                        emit_speech_segment(&app, b64);
                     }
                     speech_buffer.clear();
                     in_speech = false;
@@ -219,7 +224,7 @@ async fn run_vad_capture(
                             let normalized_buffer = normalize_audio_level(&speech_buffer, 0.1);
                             if let Ok(b64) = samples_to_wav_b64(sr, &normalized_buffer) {
                                 // let duration = speech_buffer.len() as f32 / sr as f32;
-                                let _ = app.emit("speech-detected", b64);
+                                emit_speech_segment(&app, b64);
                             } else {
                                 error!("Failed to encode speech to WAV");
                                 let _ = app.emit("audio-encoding-error", "Failed to encode speech");
@@ -268,6 +273,7 @@ async fn run_continuous_capture(
 
     // Pre-allocate buffer to prevent reallocations
     let mut audio_buffer = Vec::with_capacity(max_samples);
+    let mut stream_frame: Vec<f32> = Vec::with_capacity(config.hop_size);
     let start_time = Instant::now();
     let max_duration = Duration::from_secs(config.max_recording_duration_secs);
 
@@ -303,6 +309,12 @@ async fn run_continuous_capture(
 
                         audio_buffer.push(sample);
 
+                        stream_frame.push(sample);
+                        if stream_frame.len() >= config.hop_size {
+                            push_frame(&app, StreamSource::Speaker, sr, &stream_frame);
+                            stream_frame.clear();
+                        }
+
                         let elapsed = start_time.elapsed();
 
                         // Emit progress every second
@@ -344,7 +356,7 @@ async fn run_continuous_capture(
 
         match samples_to_wav_b64(sr, &cleaned_audio) {
             Ok(b64) => {
-                let _ = app.emit("speech-detected", b64);
+                emit_speech_segment(&app, b64);
             }
             Err(e) => {
                 error!("Failed to encode continuous audio: {}", e);
@@ -359,6 +371,13 @@ async fn run_continuous_capture(
     let _ = app.emit("continuous-recording-stopped", ());
 }
 
+// Hand the segment to the Rust transcription queue, or to the frontend when it's off
+fn emit_speech_segment(app: &AppHandle, b64: String) {
+    if !enqueue_segment(app, StreamSource::Speaker, &b64) {
+        let _ = app.emit("speech-detected", b64);
+    }
+}
+
 // Apply noise gate
 fn apply_noise_gate(samples: &[f32], threshold: f32) -> Vec<f32> {
     const KNEE_RATIO: f32 = 3.0; // Compression ratio for soft knee
@@ -0,0 +1,234 @@
+// Server-sent events decoder shared by the streaming chat providers.
+// Follows the WHATWG event-stream parsing rules: LF, CR and CRLF line endings,
+// `:` comments, multi-line `data`, and the `event`/`id`/`retry` fields.
+// Input is buffered as bytes and only complete lines are decoded, so a UTF-8
+// character split across network chunks is never mangled.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SseEvent {
+    // "message" unless the stream sent an `event:` field
+    pub event: String,
+    pub data: String,
+    // Last event id seen on the stream (persists across events, per spec)
+    pub id: Option<String>,
+    // Reconnection time if a `retry:` field arrived since the previous event
+    pub retry: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+pub struct SseDecoder {
+    pending: Vec<u8>,
+    // Previous chunk ended in '\r': a leading '\n' belongs to that CRLF
+    skip_lf: bool,
+    started: bool,
+    event: String,
+    data: String,
+    has_data: bool,
+    last_id: Option<String>,
+    retry: Option<u64>,
+}
+
+impl SseDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed raw bytes from the network, returning every event they complete.
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        let mut events = Vec::new();
+        let mut bytes = chunk;
+
+        if self.skip_lf && !bytes.is_empty() {
+            if bytes[0] == b'\n' {
+                bytes = &bytes[1..];
+            }
+            self.skip_lf = false;
+        }
+
+        while let Some(pos) = bytes.iter().position(|b| *b == b'\n' || *b == b'\r') {
+            self.pending.extend_from_slice(&bytes[..pos]);
+            let line = std::mem::take(&mut self.pending);
+            if let Some(event) = self.process_line(&line) {
+                events.push(event);
+            }
+
+            let is_cr = bytes[pos] == b'\r';
+            bytes = &bytes[pos + 1..];
+            if is_cr {
+                match bytes.first() {
+                    Some(b'\n') => bytes = &bytes[1..],
+                    Some(_) => {}
+                    None => self.skip_lf = true,
+                }
+            }
+        }
+
+        self.pending.extend_from_slice(bytes);
+        events
+    }
+
+    /// Flush at end of stream. The spec discards an event that wasn't
+    /// terminated by a blank line, but several providers close the connection
+    /// right after their last `data:` line, so we dispatch it instead.
+    pub fn finish(&mut self) -> Option<SseEvent> {
+        let mut event = None;
+        if !self.pending.is_empty() {
+            let line = std::mem::take(&mut self.pending);
+            event = self.process_line(&line);
+        }
+        event.or_else(|| self.dispatch())
+    }
+
+    fn process_line(&mut self, raw: &[u8]) -> Option<SseEvent> {
+        let mut raw = raw;
+        if !self.started {
+            self.started = true;
+            raw = raw.strip_prefix("\u{feff}".as_bytes()).unwrap_or(raw);
+        }
+
+        if raw.is_empty() {
+            return self.dispatch();
+        }
+
+        let line = String::from_utf8_lossy(raw);
+        if line.starts_with(':') {
+            return None;
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line.as_ref(), ""),
+        };
+
+        match field {
+            "event" => self.event = value.to_string(),
+            "data" => {
+                if self.has_data {
+                    self.data.push('\n');
+                }
+                self.data.push_str(value);
+                self.has_data = true;
+            }
+            "id" if !value.contains('\0') => self.last_id = Some(value.to_string()),
+            "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+                self.retry = value.parse().ok();
+            }
+            _ => {}
+        }
+
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = std::mem::take(&mut self.event);
+        if !self.has_data {
+            return None;
+        }
+
+        self.has_data = false;
+        Some(SseEvent {
+            event: if event.is_empty() {
+                "message".to_string()
+            } else {
+                event
+            },
+            data: std::mem::take(&mut self.data),
+            id: self.last_id.clone(),
+            retry: self.retry.take(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(chunks: &[&[u8]]) -> Vec<SseEvent> {
+        let mut decoder = SseDecoder::new();
+        let mut events: Vec<SseEvent> = chunks
+            .iter()
+            .flat_map(|chunk| decoder.feed(chunk))
+            .collect();
+        events.extend(decoder.finish());
+        events
+    }
+
+    fn data(events: &[SseEvent]) -> Vec<&str> {
+        events.iter().map(|event| event.data.as_str()).collect()
+    }
+
+    #[test]
+    fn crlf_split_across_chunks() {
+        let mut decoder = SseDecoder::new();
+        assert!(decoder.feed(b"data: one\r").is_empty());
+        let events = decoder.feed(b"\n\r\ndata: two\r\n\r\n");
+        assert_eq!(data(&events), ["one", "two"]);
+        assert_eq!(decoder.finish(), None);
+    }
+
+    #[test]
+    fn utf8_character_split_across_chunks() {
+        let text = "data: caf\u{e9} \u{1f600}\n\n".as_bytes();
+        // Split inside the two-byte é and again inside the four-byte emoji
+        let events = decode(&[&text[..10], &text[10..13], &text[13..]]);
+        assert_eq!(data(&events), ["caf\u{e9} \u{1f600}"]);
+    }
+
+    #[test]
+    fn comments_are_ignored() {
+        let events = decode(&[b": keep-alive\n\n:ping\ndata: hi\n\n"]);
+        assert_eq!(data(&events), ["hi"]);
+    }
+
+    #[test]
+    fn multi_line_data_is_joined() {
+        let events = decode(&[b"data: first\ndata:second\ndata\n\n"]);
+        assert_eq!(data(&events), ["first\nsecond\n"]);
+    }
+
+    #[test]
+    fn event_id_and_retry_fields() {
+        let events = decode(&[
+            b"event: delta\nid: 7\nretry: 1500\ndata: a\n\n",
+            b"retry: 2s\ndata: b\n\n",
+        ]);
+
+        assert_eq!(
+            events,
+            [
+                SseEvent {
+                    event: "delta".to_string(),
+                    data: "a".to_string(),
+                    id: Some("7".to_string()),
+                    retry: Some(1500),
+                },
+                // The id carries over; the invalid retry and missing event don't
+                SseEvent {
+                    event: "message".to_string(),
+                    data: "b".to_string(),
+                    id: Some("7".to_string()),
+                    retry: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn leading_bom_is_stripped() {
+        let events = decode(&["\u{feff}data: hi\n\n".as_bytes()]);
+        assert_eq!(data(&events), ["hi"]);
+
+        // Only at the very start of the stream
+        let events = decode(&["data: a\n\n\u{feff}data: b\n\n".as_bytes()]);
+        assert_eq!(data(&events), ["a"]);
+    }
+
+    #[test]
+    fn finish_flushes_unterminated_event() {
+        let mut decoder = SseDecoder::new();
+        assert!(decoder.feed(b"data: {\"done\":true}").is_empty());
+        let event = decoder.finish().unwrap();
+        assert_eq!(event.data, "{\"done\":true}");
+        assert_eq!(decoder.finish(), None);
+    }
+}
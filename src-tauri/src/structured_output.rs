@@ -0,0 +1,72 @@
+// Checks chat answers requested with a `response_format` against its JSON
+// schema. Providers without a native JSON mode sometimes wrap the value in a
+// markdown fence or a sentence of prose, so the JSON is located first.
+use serde_json::Value;
+
+use crate::chat_adapters::ResponseFormat;
+
+// Enough for the model to fix its answer without flooding the retry prompt
+const MAX_REPORTED_ERRORS: usize = 5;
+
+/// Reject schemas that can't be compiled before anything is sent.
+pub fn check_schema(format: &ResponseFormat) -> Result<(), String> {
+    let valid_name = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+    if format.name.is_empty() || !format.name.chars().all(valid_name) {
+        return Err(
+            "Invalid response_format name: must only contain letters, digits, _ and -".to_string(),
+        );
+    }
+    jsonschema::validator_for(&format.schema)
+        .map(|_| ())
+        .map_err(|e| format!("Invalid response_format schema: {}", e))
+}
+
+fn extract_json(text: &str) -> &str {
+    let trimmed = text.trim();
+
+    // ```json ... ```
+    if let Some(fenced) = trimmed.strip_prefix("```") {
+        let body = fenced.split_once('\n').map_or("", |(_, body)| body);
+        return body.trim_end().strip_suffix("```").unwrap_or(body).trim();
+    }
+
+    // Prose around an object or array
+    match (trimmed.find(['{', '[']), trimmed.rfind(['}', ']'])) {
+        (Some(start), Some(end)) if start < end => &trimmed[start..=end],
+        _ => trimmed,
+    }
+}
+
+/// The parsed answer, or what's wrong with it.
+pub fn validate(format: &ResponseFormat, text: &str) -> Result<Value, Vec<String>> {
+    let value: Value = serde_json::from_str(extract_json(text))
+        .map_err(|e| vec![format!("the answer is not valid JSON ({})", e)])?;
+    let validator = jsonschema::validator_for(&format.schema)
+        .map_err(|e| vec![format!("the schema is invalid ({})", e)])?;
+
+    let errors: Vec<String> = validator
+        .iter_errors(&value)
+        .take(MAX_REPORTED_ERRORS)
+        .map(|error| {
+            let path = error.instance_path.to_string();
+            if path.is_empty() {
+                error.to_string()
+            } else {
+                format!("{}: {}", path, error)
+            }
+        })
+        .collect();
+
+    if errors.is_empty() {
+        Ok(value)
+    } else {
+        Err(errors)
+    }
+}
+
+pub fn correction_prompt(errors: &[String]) -> String {
+    format!(
+        "Your previous answer did not match the required JSON schema:\n- {}\nReply again with only the corrected JSON.",
+        errors.join("\n- ")
+    )
+}
@@ -0,0 +1,368 @@
+// User-defined STT providers executed natively. This mirrors the frontend's
+// curl-template providers, but lets native mic/speaker segments go straight
+// from the capture loop to the provider without a base64 trip through the webview.
+use crate::api::{self, TranscriptionResult};
+use crate::config_store::{JsonConfig, JsonConfigStore};
+use crate::http::{self, EndpointKind};
+use crate::usage_budget::{self, BudgetRequest};
+use crate::vocabulary;
+use base64::{engine::general_purpose, Engine as _};
+use reqwest::multipart::{Form, Part};
+use reqwest::RequestBuilder;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use tauri::{AppHandle, Manager};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SttAuth {
+    None,
+    Bearer { token: String },
+    Header { name: String, value: String },
+    Query { name: String, value: String },
+    Basic { username: String, password: String },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SttBodyFormat {
+    // multipart/form-data with the WAV as a file part (OpenAI/Groq style)
+    Multipart,
+    // JSON body template, "{{AUDIO_BASE64}}" / "{{AUDIO_MIME}}" strings are substituted
+    Json,
+    // Raw WAV bytes as the request body (Deepgram style)
+    Raw,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SttKeyValue {
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomSttProvider {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    pub auth: SttAuth,
+    #[serde(default)]
+    pub headers: Vec<SttKeyValue>,
+    pub body_format: SttBodyFormat,
+    #[serde(default = "default_file_field")]
+    pub file_field: String,
+    #[serde(default)]
+    pub form_fields: Vec<SttKeyValue>,
+    #[serde(default)]
+    pub json_body: Option<serde_json::Value>,
+    // Dot path to the transcript, e.g. "results.channels.0.alternatives.0.transcript".
+    // Empty means "use the standard text/segments parser".
+    #[serde(default)]
+    pub response_path: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct SttProviderStore {
+    pub active_provider_id: Option<String>,
+    pub providers: Vec<CustomSttProvider>,
+}
+
+impl JsonConfig for SttProviderStore {
+    const FILE_NAME: &'static str = "stt_providers.json";
+    const LABEL: &'static str = "STT provider";
+}
+
+#[derive(Default)]
+pub struct SttProviderState {
+    store: JsonConfigStore<SttProviderStore>,
+}
+
+const AUDIO_BASE64_PLACEHOLDER: &str = "{{AUDIO_BASE64}}";
+const AUDIO_MIME_PLACEHOLDER: &str = "{{AUDIO_MIME}}";
+const AUDIO_MIME: &str = "audio/wav";
+
+fn default_file_field() -> String {
+    "file".to_string()
+}
+
+fn load_store(app: &AppHandle) -> Result<SttProviderStore, String> {
+    app.state::<SttProviderState>().store.load(app)
+}
+
+fn save_store(app: &AppHandle, store: SttProviderStore) -> Result<(), String> {
+    app.state::<SttProviderState>().store.save(app, store)
+}
+
+pub fn active_provider(app: &AppHandle) -> Option<CustomSttProvider> {
+    let store = match load_store(app) {
+        Ok(store) => store,
+        Err(e) => {
+            tracing::warn!("Failed to load STT providers: {}", e);
+            return None;
+        }
+    };
+
+    let active_id = store.active_provider_id.as_ref()?;
+    store
+        .providers
+        .into_iter()
+        .find(|provider| &provider.id == active_id)
+}
+
+fn validate_provider(provider: &CustomSttProvider) -> Result<(), String> {
+    if provider.name.trim().is_empty() {
+        return Err("Provider name cannot be empty".to_string());
+    }
+
+    let url = reqwest::Url::parse(provider.url.trim())
+        .map_err(|e| format!("Invalid provider URL: {}", e))?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err("Provider URL must use http or https".to_string());
+    }
+
+    match provider.body_format {
+        SttBodyFormat::Multipart if provider.file_field.trim().is_empty() => {
+            Err("Multipart providers need a file field name".to_string())
+        }
+        SttBodyFormat::Json => {
+            let body = provider
+                .json_body
+                .as_ref()
+                .ok_or("JSON providers need a body template")?;
+            if !body.to_string().contains(AUDIO_BASE64_PLACEHOLDER) {
+                return Err(format!(
+                    "JSON body template must contain \"{}\"",
+                    AUDIO_BASE64_PLACEHOLDER
+                ));
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Sends WAV bytes to a custom provider and runs the usual post-processing
+/// (hallucination filter + vocabulary rules) on the result.
+pub async fn transcribe_with_custom_provider(
+    app: &AppHandle,
+    provider: &CustomSttProvider,
+    audio_bytes: &[u8],
+) -> Result<TranscriptionResult, String> {
+    let audio_seconds = api::wav_duration_seconds(audio_bytes).unwrap_or(0.0);
+    usage_budget::check(app, BudgetRequest::Transcription { audio_seconds }).await?;
+
+    let url = provider.url.trim();
+    let started = Instant::now();
+    let response = http::send(app, EndpointKind::Transcription, &http::provider_key(url), |client| {
+        build_request(client, provider, audio_bytes)
+    })
+    .await
+    .map_err(|e| format!("Transcription request failed to send: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "Unable to read transcription error response".to_string());
+        return Err(format!(
+            "Transcription request returned {} with body: {}",
+            status, error_text
+        ));
+    }
+
+    let body_text = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read transcription response: {}", e))?;
+
+    if body_text.trim().is_empty() {
+        return Err("Transcription response was empty".to_string());
+    }
+
+    let mut result = extract_transcription(&body_text, provider.response_path.trim())?;
+    let vocabulary = vocabulary::active_profile(app);
+    api::finalize_transcription(app, &mut result, audio_bytes, vocabulary.as_deref());
+    // Logged under the provider's name, which pricing overrides can match
+    api::record_transcription_usage(
+        app,
+        Some("custom".to_string()),
+        &provider.name,
+        &result,
+        audio_bytes,
+        started,
+    );
+    Ok(result)
+}
+
+// Called once per attempt: multipart bodies are consumed on send
+fn build_request(
+    client: &reqwest::Client,
+    provider: &CustomSttProvider,
+    audio_bytes: &[u8],
+) -> Result<RequestBuilder, String> {
+    let mut request = client.post(provider.url.trim());
+
+    request = match &provider.auth {
+        SttAuth::None => request,
+        SttAuth::Bearer { token } => request.bearer_auth(token),
+        SttAuth::Header { name, value } => request.header(name.as_str(), value.as_str()),
+        SttAuth::Query { name, value } => request.query(&[(name.as_str(), value.as_str())]),
+        SttAuth::Basic { username, password } => request.basic_auth(username, Some(password)),
+    };
+
+    for header in &provider.headers {
+        if !header.key.trim().is_empty() {
+            request = request.header(header.key.trim(), header.value.as_str());
+        }
+    }
+
+    request = match provider.body_format {
+        SttBodyFormat::Multipart => {
+            let audio_part = Part::bytes(audio_bytes.to_vec())
+                .file_name("audio.wav")
+                .mime_str(AUDIO_MIME)
+                .map_err(|e| format!("Failed to prepare audio payload: {}", e))?;
+            let mut form = Form::new().part(provider.file_field.trim().to_string(), audio_part);
+            for field in &provider.form_fields {
+                if !field.key.trim().is_empty() {
+                    form = form.text(field.key.trim().to_string(), field.value.clone());
+                }
+            }
+            request.multipart(form)
+        }
+        SttBodyFormat::Json => {
+            let template = provider
+                .json_body
+                .clone()
+                .ok_or("JSON providers need a body template")?;
+            let audio_base64 = general_purpose::STANDARD.encode(audio_bytes);
+            request.json(&fill_body_template(template, &audio_base64))
+        }
+        SttBodyFormat::Raw => request
+            .header("Content-Type", AUDIO_MIME)
+            .body(audio_bytes.to_vec()),
+    };
+
+    Ok(request)
+}
+
+fn extract_transcription(body_text: &str, response_path: &str) -> Result<TranscriptionResult, String> {
+    if response_path.is_empty() {
+        return Ok(api::parse_transcription_response(body_text));
+    }
+
+    let json: serde_json::Value = serde_json::from_str(body_text)
+        .map_err(|e| format!("Failed to parse transcription response: {}", e))?;
+    let text = get_by_path(&json, response_path)
+        .and_then(|value| value.as_str())
+        .ok_or_else(|| format!("No transcript found at \"{}\" in provider response", response_path))?;
+
+    Ok(TranscriptionResult {
+        text: text.to_string(),
+        ..Default::default()
+    })
+}
+
+// Dot path lookup, numeric segments index into arrays
+fn get_by_path<'a>(value: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    path.split('.')
+        .filter(|segment| !segment.is_empty())
+        .try_fold(value, |current, segment| match current {
+            serde_json::Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+            serde_json::Value::Object(map) => map.get(segment),
+            _ => None,
+        })
+}
+
+fn fill_body_template(value: serde_json::Value, audio_base64: &str) -> serde_json::Value {
+    match value {
+        serde_json::Value::String(text) => serde_json::Value::String(
+            text.replace(AUDIO_BASE64_PLACEHOLDER, audio_base64)
+                .replace(AUDIO_MIME_PLACEHOLDER, AUDIO_MIME),
+        ),
+        serde_json::Value::Array(items) => serde_json::Value::Array(
+            items
+                .into_iter()
+                .map(|item| fill_body_template(item, audio_base64))
+                .collect(),
+        ),
+        serde_json::Value::Object(map) => serde_json::Value::Object(
+            map.into_iter()
+                .map(|(key, item)| (key, fill_body_template(item, audio_base64)))
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+#[tauri::command]
+pub fn get_stt_providers(app: AppHandle) -> Result<SttProviderStore, String> {
+    load_store(&app)
+}
+
+#[tauri::command]
+pub fn save_stt_provider(
+    app: AppHandle,
+    provider: CustomSttProvider,
+) -> Result<CustomSttProvider, String> {
+    validate_provider(&provider)?;
+
+    let mut provider = provider;
+    if provider.id.trim().is_empty() {
+        provider.id = Uuid::new_v4().to_string();
+    }
+
+    let mut store = load_store(&app)?;
+    match store.providers.iter_mut().find(|p| p.id == provider.id) {
+        Some(existing) => *existing = provider.clone(),
+        None => store.providers.push(provider.clone()),
+    }
+    save_store(&app, store)?;
+
+    Ok(provider)
+}
+
+#[tauri::command]
+pub fn delete_stt_provider(app: AppHandle, id: String) -> Result<(), String> {
+    let mut store = load_store(&app)?;
+    store.providers.retain(|provider| provider.id != id);
+    if store.active_provider_id.as_deref() == Some(id.as_str()) {
+        store.active_provider_id = None;
+    }
+    save_store(&app, store)
+}
+
+#[tauri::command]
+pub fn set_active_stt_provider(app: AppHandle, id: Option<String>) -> Result<(), String> {
+    let mut store = load_store(&app)?;
+    if let Some(id) = id.as_ref() {
+        if !store.providers.iter().any(|provider| &provider.id == id) {
+            return Err(format!("STT provider not found: {}", id));
+        }
+    }
+    store.active_provider_id = id;
+    save_store(&app, store)
+}
+
+// Transcribe with a stored provider (the active one when no id is given)
+#[tauri::command]
+pub async fn transcribe_with_stt_provider(
+    app: AppHandle,
+    audio_base64: String,
+    provider_id: Option<String>,
+) -> Result<TranscriptionResult, String> {
+    let provider = match provider_id {
+        Some(id) => load_store(&app)?
+            .providers
+            .into_iter()
+            .find(|provider| provider.id == id)
+            .ok_or_else(|| format!("STT provider not found: {}", id))?,
+        None => active_provider(&app).ok_or("No STT provider selected")?,
+    };
+
+    let audio_bytes = api::decode_audio_base64(&audio_base64)?;
+    transcribe_with_custom_provider(&app, &provider, &audio_bytes).await
+}
@@ -0,0 +1,660 @@
+// Streaming speech-to-text over WebSocket. Capture loops push raw frames here
+// while a session is open, so partial transcripts arrive while the user is
+// still speaking instead of after the silence timeout + WAV upload.
+use futures_util::{SinkExt, StreamExt};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{error, warn};
+
+use crate::usage_budget::{self, BudgetRequest};
+use crate::usage_ledger::{self, UsageKind, UsageRecord};
+use crate::{transcript_log, vocabulary};
+
+// How long to wait for the provider to flush final results after capture stops
+const CLOSE_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamingProtocol {
+    Deepgram,
+    AssemblyAi,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamSource {
+    #[default]
+    Speaker,
+    Mic,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamingSttConfig {
+    pub protocol: StreamingProtocol,
+    pub url: String,
+    pub api_key: String,
+    #[serde(default = "default_stream_sample_rate")]
+    pub sample_rate: u32,
+    #[serde(default)]
+    pub source: StreamSource,
+    // Extra query parameters (language, model, keywords, ...) appended to the URL
+    #[serde(default)]
+    pub query: HashMap<String, String>,
+}
+
+fn default_stream_sample_rate() -> u32 {
+    16000
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TranscriptEvent {
+    pub text: String,
+    pub is_final: bool,
+    pub source: StreamSource,
+}
+
+struct StreamSession {
+    source: StreamSource,
+    frames: UnboundedSender<(u32, Vec<f32>)>,
+    task: JoinHandle<()>,
+}
+
+#[derive(Default)]
+pub struct SttStreamState {
+    session: Mutex<Option<StreamSession>>,
+}
+
+/// Forward a chunk of mono f32 samples to the open streaming session, if any.
+/// Cheap no-op when no session is running or it listens to another source.
+pub fn push_frame(app: &AppHandle, source: StreamSource, sample_rate: u32, samples: &[f32]) {
+    if samples.is_empty() {
+        return;
+    }
+
+    let Some(state) = app.try_state::<SttStreamState>() else {
+        return;
+    };
+
+    let guard = match state.session.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    if let Some(session) = guard.as_ref() {
+        if session.source == source {
+            let _ = session.frames.send((sample_rate, samples.to_vec()));
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn start_transcript_stream(
+    app: AppHandle,
+    config: StreamingSttConfig,
+) -> Result<(), String> {
+    if config.api_key.trim().is_empty() {
+        return Err("Streaming transcription requires an API key".to_string());
+    }
+    if !(8000..=48000).contains(&config.sample_rate) {
+        return Err(format!(
+            "Invalid sample_rate: {}. Expected 8000-48000 Hz",
+            config.sample_rate
+        ));
+    }
+    // Nothing sent yet: this only refuses to start once today's audio is used up
+    usage_budget::check(&app, BudgetRequest::Transcription { audio_seconds: 0.0 }).await?;
+
+    let state = app.state::<SttStreamState>();
+    let mut guard = state
+        .session
+        .lock()
+        .map_err(|e| format!("Failed to acquire stream lock: {}", e))?;
+
+    if let Some(session) = guard.as_ref() {
+        if !session.task.is_finished() {
+            return Err("Transcript stream already running".to_string());
+        }
+    }
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    let source = config.source;
+    let task = tokio::spawn(run_stream_session(app.clone(), config, rx));
+
+    *guard = Some(StreamSession {
+        source,
+        frames: tx,
+        task,
+    });
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn stop_transcript_stream(app: AppHandle) -> Result<(), String> {
+    let session = {
+        let state = app.state::<SttStreamState>();
+        let mut guard = state
+            .session
+            .lock()
+            .map_err(|e| format!("Failed to acquire stream lock: {}", e))?;
+        guard.take()
+    };
+
+    if let Some(session) = session {
+        // Dropping the sender tells the session to send its close message and
+        // drain the remaining results before exiting.
+        drop(session.frames);
+        let mut task = session.task;
+        if tokio::time::timeout(CLOSE_DRAIN_TIMEOUT + Duration::from_secs(1), &mut task)
+            .await
+            .is_err()
+        {
+            warn!("Transcript stream did not shut down in time, aborting");
+            task.abort();
+        }
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn is_transcript_streaming(app: AppHandle) -> Result<bool, String> {
+    let state = app.state::<SttStreamState>();
+    let guard = state
+        .session
+        .lock()
+        .map_err(|e| format!("Failed to acquire stream lock: {}", e))?;
+    Ok(guard
+        .as_ref()
+        .map(|session| !session.task.is_finished())
+        .unwrap_or(false))
+}
+
+async fn run_stream_session(
+    app: AppHandle,
+    config: StreamingSttConfig,
+    mut frames: UnboundedReceiver<(u32, Vec<f32>)>,
+) {
+    let keyterms = vocabulary::active_profile(&app)
+        .map(|profile| vocabulary::terms(&profile.profile))
+        .unwrap_or_default();
+    let request = match build_stream_request(&config, &keyterms) {
+        Ok(request) => request,
+        Err(e) => {
+            error!("Failed to build transcript stream request: {}", e);
+            let _ = app.emit("transcript-error", e);
+            return;
+        }
+    };
+
+    let started = Instant::now();
+    let (socket, _) = match tokio_tungstenite::connect_async(request).await {
+        Ok(connected) => connected,
+        Err(e) => {
+            error!("Failed to connect transcript stream: {}", e);
+            let _ = app.emit(
+                "transcript-error",
+                format!("Failed to connect to streaming provider: {}", e),
+            );
+            return;
+        }
+    };
+
+    let connect_ms = started.elapsed().as_millis() as u64;
+    let _ = app.emit("transcript-stream-started", config.source);
+    let allowance = usage_budget::audio_allowance(&app).await;
+
+    let (mut sink, mut stream) = socket.split();
+    let mut resampler = LinearResampler::new(config.sample_rate);
+    let mut provider_closed = false;
+    let mut samples_sent: u64 = 0;
+
+    loop {
+        tokio::select! {
+            frame = frames.recv() => match frame {
+                Some((sample_rate, samples)) => {
+                    let Some((message, sample_count)) = audio_message(&mut resampler, sample_rate, &samples) else {
+                        continue;
+                    };
+                    if let Err(e) = sink.send(message).await {
+                        error!("Failed to send audio frame: {}", e);
+                        let _ = app.emit("transcript-error", format!("Streaming connection lost: {}", e));
+                        provider_closed = true;
+                        break;
+                    }
+                    samples_sent += sample_count as u64;
+
+                    let sent_seconds = samples_sent as f64 / config.sample_rate as f64;
+                    let out_of_budget = allowance
+                        .as_ref()
+                        .filter(|allowance| sent_seconds >= allowance.remaining_seconds);
+                    if let Some(allowance) = out_of_budget {
+                        // Out of daily audio: end the session as if capture had stopped
+                        let _ = app.emit("transcript-error", allowance.exceeded.clone());
+                        let _ = sink.send(Message::Text(close_message(config.protocol).into())).await;
+                        break;
+                    }
+                }
+                None => {
+                    // Capture side hung up: ask the provider to flush final results
+                    let _ = sink.send(Message::Text(close_message(config.protocol).into())).await;
+                    break;
+                }
+            },
+            message = stream.next() => match message {
+                Some(Ok(Message::Text(text))) => handle_provider_message(&app, &config, text.as_str()),
+                Some(Ok(Message::Close(_))) | None => {
+                    provider_closed = true;
+                    break;
+                }
+                Some(Ok(_)) => {}
+                Some(Err(e)) => {
+                    error!("Transcript stream error: {}", e);
+                    let _ = app.emit("transcript-error", format!("Streaming connection lost: {}", e));
+                    provider_closed = true;
+                    break;
+                }
+            }
+        }
+    }
+
+    if !provider_closed {
+        let drained = tokio::time::timeout(CLOSE_DRAIN_TIMEOUT, async {
+            while let Some(Ok(message)) = stream.next().await {
+                match message {
+                    Message::Text(text) => handle_provider_message(&app, &config, text.as_str()),
+                    Message::Close(_) => break,
+                    _ => {}
+                }
+            }
+        })
+        .await;
+
+        if drained.is_err() {
+            warn!("Streaming provider did not close the connection in time");
+        }
+        let _ = sink.close().await;
+    }
+
+    record_stream_usage(&app, &config, samples_sent, connect_ms);
+    let _ = app.emit("transcript-stream-stopped", config.source);
+}
+
+// One ledger entry per session; its latency is the connection setup time
+fn record_stream_usage(app: &AppHandle, config: &StreamingSttConfig, samples_sent: u64, connect_ms: u64) {
+    if samples_sent == 0 {
+        return;
+    }
+
+    let protocol = match config.protocol {
+        StreamingProtocol::Deepgram => "deepgram",
+        StreamingProtocol::AssemblyAi => "assemblyai",
+    };
+    let model = config
+        .query
+        .get("model")
+        .or_else(|| config.query.get("speech_model"))
+        .cloned()
+        .unwrap_or_else(|| protocol.to_string());
+    usage_ledger::record(
+        app,
+        UsageRecord {
+            kind: UsageKind::Transcription,
+            request_id: None,
+            provider: Some(protocol.to_string()),
+            model,
+            prompt_tokens: 0,
+            completion_tokens: 0,
+            audio_seconds: samples_sent as f64 / config.sample_rate.max(1) as f64,
+            latency_ms: connect_ms,
+            estimated: false,
+        },
+    );
+}
+
+fn build_stream_request(
+    config: &StreamingSttConfig,
+    keyterms: &[String],
+) -> Result<tokio_tungstenite::tungstenite::handshake::client::Request, String> {
+    let mut url =
+        Url::parse(&config.url).map_err(|e| format!("Invalid streaming URL: {}", e))?;
+
+    {
+        let mut query = url.query_pairs_mut();
+        let sample_rate = config.sample_rate.to_string();
+        match config.protocol {
+            StreamingProtocol::Deepgram => {
+                query
+                    .append_pair("encoding", "linear16")
+                    .append_pair("sample_rate", &sample_rate)
+                    .append_pair("channels", "1")
+                    .append_pair("interim_results", "true");
+                for term in keyterms {
+                    query.append_pair("keyterm", term);
+                }
+            }
+            StreamingProtocol::AssemblyAi => {
+                query
+                    .append_pair("encoding", "pcm_s16le")
+                    .append_pair("sample_rate", &sample_rate);
+                if !keyterms.is_empty() {
+                    let terms = serde_json::to_string(keyterms).unwrap_or_default();
+                    query.append_pair("keyterms_prompt", &terms);
+                }
+            }
+        }
+        for (key, value) in &config.query {
+            query.append_pair(key, value);
+        }
+    }
+
+    let mut request = url
+        .as_str()
+        .into_client_request()
+        .map_err(|e| format!("Invalid streaming request: {}", e))?;
+
+    let auth_value = match config.protocol {
+        StreamingProtocol::Deepgram => format!("Token {}", config.api_key.trim()),
+        StreamingProtocol::AssemblyAi => config.api_key.trim().to_string(),
+    };
+    let auth_header = HeaderValue::from_str(&auth_value)
+        .map_err(|e| format!("Invalid API key for streaming provider: {}", e))?;
+    request.headers_mut().insert("Authorization", auth_header);
+
+    Ok(request)
+}
+
+fn close_message(protocol: StreamingProtocol) -> &'static str {
+    match protocol {
+        StreamingProtocol::Deepgram => r#"{"type":"CloseStream"}"#,
+        StreamingProtocol::AssemblyAi => r#"{"type":"Terminate"}"#,
+    }
+}
+
+// Resampled 16-bit PCM for one captured frame, and how many samples it holds
+fn audio_message(
+    resampler: &mut LinearResampler,
+    sample_rate: u32,
+    samples: &[f32],
+) -> Option<(Message, usize)> {
+    let pcm = resampler.process(sample_rate, samples);
+    if pcm.is_empty() {
+        return None;
+    }
+    Some((Message::Binary(pcm_to_le_bytes(&pcm).into()), pcm.len()))
+}
+
+#[derive(Debug, PartialEq)]
+enum ProviderMessage {
+    Error(String),
+    Transcript { text: String, is_final: bool },
+    // Metadata, keep-alives and empty results
+    Ignored,
+}
+
+fn parse_provider_message(protocol: StreamingProtocol, text: &str) -> ProviderMessage {
+    let Ok(json) = serde_json::from_str::<serde_json::Value>(text) else {
+        warn!("Ignoring non-JSON streaming message");
+        return ProviderMessage::Ignored;
+    };
+
+    if let Some(error_msg) = json
+        .get("error")
+        .and_then(|e| e.as_str())
+        .or_else(|| json.get("err_msg").and_then(|e| e.as_str()))
+    {
+        return ProviderMessage::Error(error_msg.to_string());
+    }
+
+    match parse_transcript(protocol, &json) {
+        Some((text, is_final)) if !text.trim().is_empty() => {
+            ProviderMessage::Transcript { text, is_final }
+        }
+        _ => ProviderMessage::Ignored,
+    }
+}
+
+fn transcript_event_name(is_final: bool) -> &'static str {
+    if is_final {
+        "transcript-final"
+    } else {
+        "transcript-partial"
+    }
+}
+
+fn handle_provider_message(app: &AppHandle, config: &StreamingSttConfig, text: &str) {
+    let (transcript, is_final) = match parse_provider_message(config.protocol, text) {
+        ProviderMessage::Transcript { text, is_final } => (text, is_final),
+        ProviderMessage::Error(error_msg) => {
+            let _ = app.emit("transcript-error", error_msg);
+            return;
+        }
+        ProviderMessage::Ignored => return,
+    };
+
+    let text = match vocabulary::active_profile(app) {
+        Some(profile) => vocabulary::apply_rules(&profile, &transcript),
+        None => transcript,
+    };
+
+    if is_final {
+        transcript_log::record(app, Some(config.source), &text);
+    }
+
+    let event = TranscriptEvent {
+        text,
+        is_final,
+        source: config.source,
+    };
+    let _ = app.emit(transcript_event_name(is_final), event);
+}
+
+fn parse_transcript(protocol: StreamingProtocol, json: &serde_json::Value) -> Option<(String, bool)> {
+    match protocol {
+        StreamingProtocol::Deepgram => {
+            if json.get("type").and_then(|t| t.as_str()) != Some("Results") {
+                return None;
+            }
+            let transcript = json
+                .pointer("/channel/alternatives/0/transcript")
+                .and_then(|t| t.as_str())?;
+            let is_final = json
+                .get("is_final")
+                .and_then(|f| f.as_bool())
+                .unwrap_or(false);
+            Some((transcript.to_string(), is_final))
+        }
+        StreamingProtocol::AssemblyAi => {
+            if json.get("type").and_then(|t| t.as_str()) != Some("Turn") {
+                return None;
+            }
+            let transcript = json.get("transcript").and_then(|t| t.as_str())?;
+            let is_final = json
+                .get("end_of_turn")
+                .and_then(|f| f.as_bool())
+                .unwrap_or(false);
+            Some((transcript.to_string(), is_final))
+        }
+    }
+}
+
+fn pcm_to_le_bytes(samples: &[i16]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(samples.len() * 2);
+    for sample in samples {
+        bytes.extend_from_slice(&sample.to_le_bytes());
+    }
+    bytes
+}
+
+// Linear resampler that keeps its phase across chunks so frame boundaries
+// don't introduce clicks. Outputs 16-bit PCM at the provider's sample rate.
+struct LinearResampler {
+    target_rate: u32,
+    source_rate: u32,
+    position: f64,
+    last_sample: f32,
+}
+
+impl LinearResampler {
+    fn new(target_rate: u32) -> Self {
+        Self {
+            target_rate,
+            source_rate: 0,
+            position: 0.0,
+            last_sample: 0.0,
+        }
+    }
+
+    fn process(&mut self, source_rate: u32, input: &[f32]) -> Vec<i16> {
+        if input.is_empty() || source_rate == 0 {
+            return Vec::new();
+        }
+
+        if source_rate != self.source_rate {
+            self.source_rate = source_rate;
+            self.position = 0.0;
+            self.last_sample = input[0];
+        }
+
+        let step = source_rate as f64 / self.target_rate as f64;
+        let mut output = Vec::with_capacity((input.len() as f64 / step) as usize + 1);
+
+        // Index 0 is the last sample of the previous chunk, index n is input[n - 1]
+        while self.position < input.len() as f64 {
+            let base = self.position.floor() as usize;
+            let frac = (self.position - base as f64) as f32;
+            let a = if base == 0 {
+                self.last_sample
+            } else {
+                input[base - 1]
+            };
+            let b = input[base];
+            let value = (a + (b - a) * frac).clamp(-1.0, 1.0);
+            output.push((value * i16::MAX as f32) as i16);
+            self.position += step;
+        }
+
+        self.position -= input.len() as f64;
+        self.last_sample = input[input.len() - 1];
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    const PARTIAL: &str =
+        r#"{"type":"Results","is_final":false,"channel":{"alternatives":[{"transcript":"hello"}]}}"#;
+    const FINAL: &str =
+        r#"{"type":"Results","is_final":true,"channel":{"alternatives":[{"transcript":"hello world"}]}}"#;
+
+    // A provider stand-in: counts the audio bytes it receives, answers with a
+    // partial and a final result once the client asks it to close
+    async fn mock_provider(listener: TcpListener) -> usize {
+        let (tcp, _) = listener.accept().await.unwrap();
+        let mut socket = tokio_tungstenite::accept_async(tcp).await.unwrap();
+        let mut audio_bytes = 0;
+        while let Some(Ok(message)) = socket.next().await {
+            match message {
+                Message::Binary(bytes) => audio_bytes += bytes.len(),
+                Message::Text(text) if text.as_str() == close_message(StreamingProtocol::Deepgram) => {
+                    socket.send(Message::Text(PARTIAL.into())).await.unwrap();
+                    socket.send(Message::Text(FINAL.into())).await.unwrap();
+                    socket.close(None).await.unwrap();
+                    break;
+                }
+                _ => {}
+            }
+        }
+        audio_bytes
+    }
+
+    #[tokio::test]
+    async fn streams_resampled_audio_and_maps_results() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let provider = tokio::spawn(mock_provider(listener));
+
+        let config = StreamingSttConfig {
+            protocol: StreamingProtocol::Deepgram,
+            url: format!("ws://{}/listen", addr),
+            api_key: "test".to_string(),
+            sample_rate: 16000,
+            source: StreamSource::Mic,
+            query: HashMap::new(),
+        };
+        let request = build_stream_request(&config, &[]).unwrap();
+        let (mut socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+
+        // One second of 48 kHz capture in 10 ms frames
+        let mut resampler = LinearResampler::new(config.sample_rate);
+        let frame: Vec<f32> = (0..480).map(|i| (i as f32 / 480.0) - 0.5).collect();
+        let mut samples_sent = 0;
+        for _ in 0..100 {
+            let (message, count) = audio_message(&mut resampler, 48000, &frame).unwrap();
+            socket.send(message).await.unwrap();
+            samples_sent += count;
+        }
+        socket
+            .send(Message::Text(close_message(config.protocol).into()))
+            .await
+            .unwrap();
+
+        let mut events = Vec::new();
+        while let Some(Ok(message)) = socket.next().await {
+            if let Message::Text(text) = message {
+                if let ProviderMessage::Transcript { text, is_final } =
+                    parse_provider_message(config.protocol, text.as_str())
+                {
+                    events.push((transcript_event_name(is_final), text));
+                }
+            }
+        }
+
+        assert_eq!(samples_sent, 16000);
+        assert_eq!(provider.await.unwrap(), samples_sent * 2);
+        assert_eq!(
+            events,
+            vec![
+                ("transcript-partial", "hello".to_string()),
+                ("transcript-final", "hello world".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_provider_messages() {
+        let turn = r#"{"type":"Turn","transcript":"done","end_of_turn":true}"#;
+        assert_eq!(
+            parse_provider_message(StreamingProtocol::AssemblyAi, turn),
+            ProviderMessage::Transcript {
+                text: "done".to_string(),
+                is_final: true
+            }
+        );
+        assert_eq!(
+            parse_provider_message(StreamingProtocol::Deepgram, r#"{"err_msg":"bad key"}"#),
+            ProviderMessage::Error("bad key".to_string())
+        );
+        let empty = r#"{"type":"Results","channel":{"alternatives":[{"transcript":" "}]}}"#;
+        assert_eq!(
+            parse_provider_message(StreamingProtocol::Deepgram, empty),
+            ProviderMessage::Ignored
+        );
+        assert_eq!(
+            parse_provider_message(StreamingProtocol::Deepgram, r#"{"type":"Metadata"}"#),
+            ProviderMessage::Ignored
+        );
+    }
+}
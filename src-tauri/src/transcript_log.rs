@@ -0,0 +1,93 @@
+// In-memory log of recent final transcripts, so Rust-side features (chat
+// tools, prompt context) can read what was said without asking the frontend.
+// Fed by every transcription path: uploads, the queue and streaming STT.
+use crate::stt_stream::StreamSource;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager};
+
+// Older entries are dropped; an hour of meeting audio is well under this
+const MAX_ENTRIES: usize = 2000;
+const MAX_AGE: Duration = Duration::from_secs(2 * 60 * 60);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TranscriptEntry {
+    pub text: String,
+    // None for uploads, where the caller doesn't say which device it came from
+    pub source: Option<StreamSource>,
+    pub timestamp_ms: u64,
+}
+
+#[derive(Default)]
+pub struct TranscriptLogState {
+    entries: Mutex<VecDeque<TranscriptEntry>>,
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+pub fn record(app: &AppHandle, source: Option<StreamSource>, text: &str) {
+    let text = text.trim();
+    if text.is_empty() {
+        return;
+    }
+
+    let Some(state) = app.try_state::<TranscriptLogState>() else {
+        return;
+    };
+    let mut entries = match state.entries.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    let now = now_ms();
+    let cutoff = now.saturating_sub(MAX_AGE.as_millis() as u64);
+    while entries
+        .front()
+        .is_some_and(|entry| entry.timestamp_ms < cutoff || entries.len() >= MAX_ENTRIES)
+    {
+        entries.pop_front();
+    }
+    entries.push_back(TranscriptEntry {
+        text: text.to_string(),
+        source,
+        timestamp_ms: now,
+    });
+}
+
+/// Entries from the last `window`, oldest first.
+pub fn recent(app: &AppHandle, window: Duration) -> Vec<TranscriptEntry> {
+    let Some(state) = app.try_state::<TranscriptLogState>() else {
+        return Vec::new();
+    };
+    let entries = match state.entries.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    let cutoff = now_ms().saturating_sub(window.as_millis() as u64);
+    entries
+        .iter()
+        .filter(|entry| entry.timestamp_ms >= cutoff)
+        .cloned()
+        .collect()
+}
+
+/// Plain-text rendering with a speaker label per line.
+pub fn format_entries(entries: &[TranscriptEntry]) -> String {
+    entries
+        .iter()
+        .map(|entry| match entry.source {
+            Some(StreamSource::Speaker) => format!("Them: {}", entry.text),
+            Some(StreamSource::Mic) => format!("Me: {}", entry.text),
+            None => entry.text.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,230 @@
+// Rust-side transcription queue. When enabled, capture loops hand finished
+// speech segments here instead of emitting `speech-detected`; segments are
+// transcribed with bounded concurrency and results are emitted strictly in
+// capture order as `transcription-result` events. Retries happen in the
+// shared HTTP layer, so each segment gets exactly one transcription call here.
+use crate::api::{self, TranscriptionResult};
+use crate::config_store::{JsonConfig, JsonConfigStore};
+use crate::stt_providers;
+use crate::stt_stream::StreamSource;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::Semaphore;
+use tracing::warn;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptionQueueConfig {
+    pub enabled: bool,
+    pub max_concurrency: usize,
+    pub max_pending: usize,
+}
+
+impl Default for TranscriptionQueueConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,     // Opt-in: the frontend still owns STT by default
+            max_concurrency: 2, // Enough to hide latency without hammering the provider
+            max_pending: 16,    // ~16 utterances behind means something is wrong
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TranscriptionQueueEvent {
+    pub seq: u64,
+    pub source: StreamSource,
+    pub result: Option<TranscriptionResult>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TranscriptionQueueStatus {
+    // Segments accepted but not yet emitted (waiting, running, or held for ordering)
+    pub depth: u64,
+    pub in_flight: usize,
+}
+
+#[derive(Default)]
+struct QueueInner {
+    next_seq: u64,
+    next_emit: u64,
+    in_flight: usize,
+    completed: BTreeMap<u64, TranscriptionQueueEvent>,
+}
+
+impl QueueInner {
+    fn status(&self) -> TranscriptionQueueStatus {
+        TranscriptionQueueStatus {
+            depth: self.next_seq - self.next_emit,
+            in_flight: self.in_flight,
+        }
+    }
+}
+
+impl JsonConfig for TranscriptionQueueConfig {
+    const FILE_NAME: &'static str = "transcription_queue.json";
+    const LABEL: &'static str = "transcription queue";
+}
+
+#[derive(Default)]
+pub struct TranscriptionQueueState {
+    config: JsonConfigStore<TranscriptionQueueConfig>,
+    inner: Mutex<QueueInner>,
+    // Sized for max_concurrency; replaced when the setting changes, and
+    // in-flight jobs keep their permits from the old semaphore
+    permits: Mutex<Option<(usize, Arc<Semaphore>)>>,
+}
+
+impl TranscriptionQueueState {
+    fn permits(&self, max_concurrency: usize) -> Arc<Semaphore> {
+        let mut permits = match self.permits.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        match permits.as_ref() {
+            Some((size, semaphore)) if *size == max_concurrency => semaphore.clone(),
+            _ => {
+                let semaphore = Arc::new(Semaphore::new(max_concurrency));
+                *permits = Some((max_concurrency, semaphore.clone()));
+                semaphore
+            }
+        }
+    }
+}
+
+/// Hand a WAV (base64) speech segment to the queue. Returns false when the
+/// queue is disabled so the caller can fall back to emitting the segment.
+pub fn enqueue_segment(app: &AppHandle, source: StreamSource, audio_base64: &str) -> bool {
+    let Some(state) = app.try_state::<TranscriptionQueueState>() else {
+        return false;
+    };
+
+    let config = state.config.load_or_default(app);
+    if !config.enabled {
+        return false;
+    }
+
+    let audio_bytes = match api::decode_audio_base64(audio_base64) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            warn!("Dropping undecodable speech segment: {}", e);
+            return true;
+        }
+    };
+
+    let seq = {
+        let mut inner = match state.inner.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        if inner.next_seq - inner.next_emit >= config.max_pending as u64 {
+            warn!("Transcription queue full, dropping segment");
+            let _ = app.emit("transcription-queue-full", inner.status());
+            return true;
+        }
+
+        let seq = inner.next_seq;
+        inner.next_seq += 1;
+        let _ = app.emit("transcription-queue-depth", inner.status());
+        seq
+    };
+
+    let permits = state.permits(config.max_concurrency);
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        // Closed semaphores never happen here, but don't wedge the ordering if they do
+        let permit = permits.acquire_owned().await.ok();
+        update_inner(&app, |inner| inner.in_flight += 1);
+
+        let outcome = transcribe_segment(&app, &audio_bytes).await;
+        drop(permit);
+
+        let event = match outcome {
+            Ok(result) => TranscriptionQueueEvent {
+                seq,
+                source,
+                result: Some(result),
+                error: None,
+            },
+            Err(error) => TranscriptionQueueEvent {
+                seq,
+                source,
+                result: None,
+                error: Some(error),
+            },
+        };
+        complete_segment(&app, event);
+    });
+
+    true
+}
+
+async fn transcribe_segment(app: &AppHandle, audio_bytes: &[u8]) -> Result<TranscriptionResult, String> {
+    match stt_providers::active_provider(app) {
+        Some(provider) => {
+            stt_providers::transcribe_with_custom_provider(app, &provider, audio_bytes).await
+        }
+        None => api::transcribe_with_user_audio_config(app, audio_bytes, false).await,
+    }
+}
+
+fn update_inner(app: &AppHandle, update: impl FnOnce(&mut QueueInner)) {
+    let state = app.state::<TranscriptionQueueState>();
+    let mut inner = match state.inner.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    update(&mut inner);
+    let _ = app.emit("transcription-queue-depth", inner.status());
+}
+
+fn complete_segment(app: &AppHandle, event: TranscriptionQueueEvent) {
+    // Emit while holding the lock so two finishing tasks can't interleave
+    update_inner(app, |inner| {
+        inner.in_flight = inner.in_flight.saturating_sub(1);
+        inner.completed.insert(event.seq, event);
+
+        while let Some(ready) = inner.completed.remove(&inner.next_emit) {
+            let _ = app.emit("transcription-result", ready);
+            inner.next_emit += 1;
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn get_transcription_queue_config(
+    app: AppHandle,
+) -> Result<TranscriptionQueueConfig, String> {
+    app.state::<TranscriptionQueueState>().config.load(&app)
+}
+
+#[tauri::command]
+pub async fn update_transcription_queue_config(
+    app: AppHandle,
+    config: TranscriptionQueueConfig,
+) -> Result<(), String> {
+    if !(1..=8).contains(&config.max_concurrency) {
+        return Err("Invalid max_concurrency: must be 1-8".to_string());
+    }
+    if config.max_pending == 0 {
+        return Err("Invalid max_pending: must be at least 1".to_string());
+    }
+
+    app.state::<TranscriptionQueueState>().config.save(&app, config)
+}
+
+#[tauri::command]
+pub async fn get_transcription_queue_status(
+    app: AppHandle,
+) -> Result<TranscriptionQueueStatus, String> {
+    let state = app.state::<TranscriptionQueueState>();
+    let inner = state
+        .inner
+        .lock()
+        .map_err(|e| format!("Failed to get transcription queue status: {}", e))?;
+    Ok(inner.status())
+}
@@ -0,0 +1,314 @@
+// Local spending and rate limits, checked before a chat or transcription
+// request is sent. Daily totals come from the usage ledger; the per-minute
+// window is kept in memory so a runaway auto-trigger loop is stopped even
+// before its requests finish and get recorded.
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::config_store::{JsonConfig, JsonConfigStore};
+use crate::usage_ledger::{self, DailyTotals};
+
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+// Share of a limit at which `usage_budget_warning` is emitted
+const WARN_RATIO: f64 = 0.8;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageBudgetConfig {
+    pub enabled: bool,
+    // Prompt + completion tokens of chat requests per local day
+    pub tokens_per_day: Option<u64>,
+    // Chat and transcription requests combined
+    pub requests_per_minute: Option<u32>,
+    pub audio_minutes_per_day: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetLimit {
+    TokensPerDay,
+    RequestsPerMinute,
+    AudioMinutesPerDay,
+}
+
+impl BudgetLimit {
+    fn label(self) -> &'static str {
+        match self {
+            BudgetLimit::TokensPerDay => "Daily token budget",
+            BudgetLimit::RequestsPerMinute => "Request rate limit",
+            BudgetLimit::AudioMinutesPerDay => "Daily audio budget",
+        }
+    }
+}
+
+/// Returned (as JSON) in place of the request's usual error string.
+#[derive(Debug, Clone, Serialize)]
+pub struct BudgetExceeded {
+    // Always "budget_exceeded", so callers can tell it apart from provider errors
+    pub code: &'static str,
+    pub limit: BudgetLimit,
+    // Usage including the rejected request
+    pub used: f64,
+    pub max: f64,
+    pub retry_after_secs: u64,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BudgetWarning {
+    pub limit: BudgetLimit,
+    pub used: f64,
+    pub max: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BudgetUsage {
+    pub limit: BudgetLimit,
+    pub used: f64,
+    pub max: Option<f64>,
+}
+
+/// What the request about to be sent will add to the totals.
+pub enum BudgetRequest {
+    Chat { estimated_tokens: u64 },
+    Transcription { audio_seconds: f64 },
+}
+
+impl JsonConfig for UsageBudgetConfig {
+    const FILE_NAME: &'static str = "usage_budget.json";
+    const LABEL: &'static str = "usage budget";
+}
+
+/// What a streaming transcription may still send today.
+pub struct AudioAllowance {
+    pub remaining_seconds: f64,
+    // JSON `BudgetExceeded` to report once the stream runs out
+    pub exceeded: String,
+}
+
+#[derive(Default)]
+pub struct UsageBudgetState {
+    config: JsonConfigStore<UsageBudgetConfig>,
+    // Start times of requests in the last minute
+    recent: Mutex<VecDeque<Instant>>,
+    // Period (day or minute) each limit last warned in, so it warns once
+    warned: Mutex<HashMap<BudgetLimit, i64>>,
+}
+
+fn load_config(app: &AppHandle) -> Result<UsageBudgetConfig, String> {
+    app.state::<UsageBudgetState>().config.load(app)
+}
+
+fn exceeded(limit: BudgetLimit, used: f64, max: f64, retry_after: Duration) -> String {
+    let retry_after_secs = retry_after.as_secs().max(1);
+    let error = BudgetExceeded {
+        code: "budget_exceeded",
+        limit,
+        used,
+        max,
+        retry_after_secs,
+        message: format!(
+            "{} reached ({:.0} of {:.0}). Try again in {} or raise the limit in settings.",
+            limit.label(),
+            used,
+            max,
+            format_wait(retry_after_secs)
+        ),
+    };
+    serde_json::to_string(&error).unwrap_or(error.message)
+}
+
+fn format_wait(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{} min", secs.div_ceil(60)),
+        _ => format!("{} h", secs.div_ceil(3600)),
+    }
+}
+
+// Warns once per period when a limit crosses WARN_RATIO
+fn warn_if_near(app: &AppHandle, limit: BudgetLimit, used: f64, max: f64, period: i64) {
+    if used < max * WARN_RATIO {
+        return;
+    }
+
+    let state = app.state::<UsageBudgetState>();
+    let mut warned = match state.warned.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    if warned.insert(limit, period) == Some(period) {
+        return;
+    }
+    let _ = app.emit("usage_budget_warning", BudgetWarning { limit, used, max });
+}
+
+// Days end at local midnight
+fn until_tomorrow(totals: &DailyTotals) -> Duration {
+    let remaining = totals.day_start_ms + DAY_MS - usage_ledger::now_ms();
+    Duration::from_millis(remaining.max(0) as u64)
+}
+
+/// Check the configured budgets before sending `request`. On success the
+/// request is counted toward the per-minute limit; on failure the error is a
+/// JSON `BudgetExceeded`. If the ledger can't be read, daily limits are
+/// skipped rather than blocking every request.
+pub async fn check(app: &AppHandle, request: BudgetRequest) -> Result<(), String> {
+    let config = app.state::<UsageBudgetState>().config.load_or_default(app);
+    if !config.enabled {
+        return Ok(());
+    }
+
+    let daily_limit = match request {
+        BudgetRequest::Chat { estimated_tokens } => config
+            .tokens_per_day
+            .map(|max| (BudgetLimit::TokensPerDay, estimated_tokens as f64, max as f64)),
+        BudgetRequest::Transcription { audio_seconds } => config
+            .audio_minutes_per_day
+            .map(|max| (BudgetLimit::AudioMinutesPerDay, audio_seconds / 60.0, max)),
+    };
+    if let Some((limit, adding, max)) = daily_limit {
+        match usage_ledger::today_totals(app).await {
+            Ok(totals) => {
+                let used = match limit {
+                    BudgetLimit::TokensPerDay => totals.tokens as f64,
+                    _ => totals.audio_seconds / 60.0,
+                } + adding;
+                if used > max {
+                    return Err(exceeded(limit, used, max, until_tomorrow(&totals)));
+                }
+                warn_if_near(app, limit, used, max, totals.day_start_ms);
+            }
+            Err(e) => tracing::warn!("Skipping daily budget check: {}", e),
+        }
+    }
+
+    count_request(app, &config)
+}
+
+/// Count one more request toward `requests_per_minute` without touching the
+/// daily limits, for requests whose tokens were already checked together.
+pub fn check_rate(app: &AppHandle) -> Result<(), String> {
+    let config = app.state::<UsageBudgetState>().config.load_or_default(app);
+    if !config.enabled {
+        return Ok(());
+    }
+    count_request(app, &config)
+}
+
+fn count_request(app: &AppHandle, config: &UsageBudgetConfig) -> Result<(), String> {
+    let state = app.state::<UsageBudgetState>();
+    let mut recent = state
+        .recent
+        .lock()
+        .map_err(|e| format!("Failed to acquire usage budget lock: {}", e))?;
+    let now = Instant::now();
+    while recent
+        .front()
+        .is_some_and(|started| now.duration_since(*started) >= RATE_WINDOW)
+    {
+        recent.pop_front();
+    }
+    if let Some(max) = config.requests_per_minute {
+        let used = recent.len() + 1;
+        if used > max as usize {
+            let retry_after = recent
+                .front()
+                .map_or(RATE_WINDOW, |oldest| RATE_WINDOW - now.duration_since(*oldest));
+            return Err(exceeded(
+                BudgetLimit::RequestsPerMinute,
+                used as f64,
+                max as f64,
+                retry_after,
+            ));
+        }
+        let minute = usage_ledger::now_ms() / RATE_WINDOW.as_millis() as i64;
+        warn_if_near(app, BudgetLimit::RequestsPerMinute, used as f64, max as f64, minute);
+    }
+    recent.push_back(now);
+    Ok(())
+}
+
+/// Audio left under `audio_minutes_per_day`, for streams that only learn
+/// their length as they go. None when no audio budget applies.
+pub async fn audio_allowance(app: &AppHandle) -> Option<AudioAllowance> {
+    let config = app.state::<UsageBudgetState>().config.load_or_default(app);
+    let max = config.audio_minutes_per_day.filter(|_| config.enabled)?;
+    let totals = usage_ledger::today_totals(app)
+        .await
+        .map_err(|e| tracing::warn!("Skipping daily audio budget: {}", e))
+        .ok()?;
+
+    Some(AudioAllowance {
+        remaining_seconds: (max * 60.0 - totals.audio_seconds).max(0.0),
+        exceeded: exceeded(BudgetLimit::AudioMinutesPerDay, max, max, until_tomorrow(&totals)),
+    })
+}
+
+#[tauri::command]
+pub fn get_usage_budget_config(app: AppHandle) -> Result<UsageBudgetConfig, String> {
+    load_config(&app)
+}
+
+#[tauri::command]
+pub fn update_usage_budget_config(app: AppHandle, config: UsageBudgetConfig) -> Result<(), String> {
+    if config.tokens_per_day == Some(0) {
+        return Err("Invalid tokens_per_day: must be greater than 0".to_string());
+    }
+    if config.requests_per_minute == Some(0) {
+        return Err("Invalid requests_per_minute: must be greater than 0".to_string());
+    }
+    if config
+        .audio_minutes_per_day
+        .is_some_and(|minutes| !minutes.is_finite() || minutes <= 0.0)
+    {
+        return Err("Invalid audio_minutes_per_day: must be greater than 0".to_string());
+    }
+
+    let state = app.state::<UsageBudgetState>();
+    state.config.save(&app, config)?;
+    // New limits get a fresh warning
+    if let Ok(mut warned) = state.warned.lock() {
+        warned.clear();
+    }
+    Ok(())
+}
+
+// Current usage against each limit, for a settings or status display
+#[tauri::command]
+pub async fn get_usage_budget_status(app: AppHandle) -> Result<Vec<BudgetUsage>, String> {
+    let config = load_config(&app)?;
+    let totals = usage_ledger::today_totals(&app).await?;
+    let requests_last_minute = {
+        let state = app.state::<UsageBudgetState>();
+        let recent = state
+            .recent
+            .lock()
+            .map_err(|e| format!("Failed to acquire usage budget lock: {}", e))?;
+        recent
+            .iter()
+            .filter(|started| started.elapsed() < RATE_WINDOW)
+            .count()
+    };
+
+    Ok(vec![
+        BudgetUsage {
+            limit: BudgetLimit::TokensPerDay,
+            used: totals.tokens as f64,
+            max: config.tokens_per_day.map(|max| max as f64),
+        },
+        BudgetUsage {
+            limit: BudgetLimit::RequestsPerMinute,
+            used: requests_last_minute as f64,
+            max: config.requests_per_minute.map(f64::from),
+        },
+        BudgetUsage {
+            limit: BudgetLimit::AudioMinutesPerDay,
+            used: totals.audio_seconds / 60.0,
+            max: config.audio_minutes_per_day,
+        },
+    ])
+}
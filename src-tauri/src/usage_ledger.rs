@@ -0,0 +1,341 @@
+// Local record of what every chat and transcription request used: tokens,
+// audio seconds, model and latency. The server-side `/api/activity` stats
+// only cover hosted models; this covers every path, including direct mode,
+// and prices it with a per-model table the user can override.
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager};
+
+use crate::config_store::{JsonConfig, JsonConfigStore};
+use crate::db;
+
+const DEFAULT_DAYS: u32 = 30;
+const DEFAULT_MONTHS: u32 = 12;
+const MAX_DAYS: u32 = 366;
+const MAX_MONTHS: u32 = 60;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct ModelPrice {
+    // USD per million tokens
+    #[serde(default)]
+    pub input_per_million: f64,
+    #[serde(default)]
+    pub output_per_million: f64,
+    // USD per minute of transcribed audio
+    #[serde(default)]
+    pub audio_per_minute: f64,
+}
+
+const fn tokens(input_per_million: f64, output_per_million: f64) -> ModelPrice {
+    ModelPrice {
+        input_per_million,
+        output_per_million,
+        audio_per_minute: 0.0,
+    }
+}
+
+const fn audio(audio_per_minute: f64) -> ModelPrice {
+    ModelPrice {
+        input_per_million: 0.0,
+        output_per_million: 0.0,
+        audio_per_minute,
+    }
+}
+
+// List prices by model-name prefix; the longest matching prefix wins
+const MODEL_PRICES: &[(&str, ModelPrice)] = &[
+    ("gpt-5-nano", tokens(0.05, 0.4)),
+    ("gpt-5-mini", tokens(0.25, 2.0)),
+    ("gpt-5", tokens(1.25, 10.0)),
+    ("gpt-4.1-nano", tokens(0.1, 0.4)),
+    ("gpt-4.1-mini", tokens(0.4, 1.6)),
+    ("gpt-4.1", tokens(2.0, 8.0)),
+    ("gpt-4o-mini-transcribe", audio(0.003)),
+    ("gpt-4o-transcribe", audio(0.006)),
+    ("gpt-4o-mini", tokens(0.15, 0.6)),
+    ("gpt-4o", tokens(2.5, 10.0)),
+    ("o3", tokens(2.0, 8.0)),
+    ("o4-mini", tokens(1.1, 4.4)),
+    ("claude-opus-4", tokens(15.0, 75.0)),
+    ("claude-sonnet-4", tokens(3.0, 15.0)),
+    ("claude-3-7-sonnet", tokens(3.0, 15.0)),
+    ("claude-3-5-sonnet", tokens(3.0, 15.0)),
+    ("claude-3-5-haiku", tokens(0.8, 4.0)),
+    ("claude-3-haiku", tokens(0.25, 1.25)),
+    ("gemini-2.5-pro", tokens(1.25, 10.0)),
+    ("gemini-2.5-flash-lite", tokens(0.1, 0.4)),
+    ("gemini-2.5-flash", tokens(0.3, 2.5)),
+    ("gemini-2.0-flash", tokens(0.1, 0.4)),
+    ("whisper-large-v3-turbo", audio(0.000_67)),
+    ("whisper-large-v3", audio(0.001_85)),
+    ("whisper-1", audio(0.006)),
+];
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UsageKind {
+    Chat,
+    Transcription,
+}
+
+impl UsageKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            UsageKind::Chat => "chat",
+            UsageKind::Transcription => "transcription",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UsageRecord {
+    pub kind: UsageKind,
+    pub request_id: Option<String>,
+    pub provider: Option<String>,
+    pub model: String,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub audio_seconds: f64,
+    pub latency_ms: u64,
+    // The provider reported no usage, so the token counts are estimates
+    pub estimated: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageAggregate {
+    // "2024-05-17" for daily aggregates, "2024-05" for monthly ones (local time)
+    pub period: String,
+    pub kind: String,
+    pub model: String,
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub audio_seconds: f64,
+    pub avg_latency_ms: u64,
+    // Requests whose token counts are estimates
+    pub estimated_requests: u64,
+    // None when the model isn't in the price table
+    pub estimated_cost_usd: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsagePricingConfig {
+    // Prices by model-name prefix, checked before the built-in table
+    pub prices: HashMap<String, ModelPrice>,
+}
+
+impl JsonConfig for UsagePricingConfig {
+    const FILE_NAME: &'static str = "usage_pricing.json";
+    const LABEL: &'static str = "usage pricing";
+}
+
+#[derive(Default)]
+pub struct UsageLedgerState {
+    pricing: JsonConfigStore<UsagePricingConfig>,
+}
+
+fn load_config(app: &AppHandle) -> Result<UsagePricingConfig, String> {
+    app.state::<UsageLedgerState>().pricing.load(app)
+}
+
+pub fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// Price of `model`, from the user's overrides or the built-in table.
+pub fn price_for(config: &UsagePricingConfig, model: &str) -> Option<ModelPrice> {
+    // "openai/gpt-4o" and "models/gemini-2.0-flash" name the same models
+    let name = model.rsplit('/').next().unwrap_or(model).to_lowercase();
+    let overrides = config
+        .prices
+        .iter()
+        .map(|(prefix, price)| (prefix.as_str(), *price));
+
+    longest_prefix_price(&name, overrides)
+        .or_else(|| longest_prefix_price(&name, MODEL_PRICES.iter().copied()))
+}
+
+fn longest_prefix_price<'a>(
+    name: &str,
+    prices: impl Iterator<Item = (&'a str, ModelPrice)>,
+) -> Option<ModelPrice> {
+    prices
+        .filter(|(prefix, _)| name.starts_with(&prefix.to_lowercase()))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, price)| price)
+}
+
+fn estimate_cost(price: &ModelPrice, prompt_tokens: u64, completion_tokens: u64, audio_seconds: f64) -> f64 {
+    prompt_tokens as f64 / 1_000_000.0 * price.input_per_million
+        + completion_tokens as f64 / 1_000_000.0 * price.output_per_million
+        + audio_seconds / 60.0 * price.audio_per_minute
+}
+
+/// Store one request's usage in the background; a failed write is logged and
+/// never affects the request itself.
+pub fn record(app: &AppHandle, record: UsageRecord) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = insert(&app, &record).await {
+            tracing::warn!("Failed to record usage: {}", e);
+        }
+    });
+}
+
+async fn insert(app: &AppHandle, record: &UsageRecord) -> Result<(), String> {
+    let pool = db::sqlite_pool(app).await?;
+    sqlx::query(
+        "INSERT INTO usage_records \
+         (kind, request_id, provider, model, prompt_tokens, completion_tokens, \
+          audio_seconds, latency_ms, estimated, timestamp) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(record.kind.as_str())
+    .bind(record.request_id.as_deref())
+    .bind(record.provider.as_deref())
+    .bind(&record.model)
+    .bind(record.prompt_tokens as i64)
+    .bind(record.completion_tokens as i64)
+    .bind(record.audio_seconds)
+    .bind(record.latency_ms as i64)
+    .bind(record.estimated)
+    .bind(now_ms())
+    .execute(&pool)
+    .await
+    .map_err(|e| format!("Failed to insert usage record: {}", e))?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DailyTotals {
+    // Local midnight, in ms since the epoch
+    pub day_start_ms: i64,
+    // Prompt + completion tokens of chat requests
+    pub tokens: u64,
+    pub audio_seconds: f64,
+}
+
+/// Usage since local midnight.
+pub async fn today_totals(app: &AppHandle) -> Result<DailyTotals, String> {
+    let pool = db::sqlite_pool(app).await?;
+    let row = sqlx::query(
+        "WITH day AS (\
+             SELECT CAST(strftime('%s', 'now', 'localtime', 'start of day', 'utc') AS INTEGER) * 1000 AS start\
+         ) \
+         SELECT day.start, \
+                COALESCE(SUM(CASE WHEN r.kind = 'chat' THEN r.prompt_tokens + r.completion_tokens END), 0), \
+                COALESCE(SUM(CASE WHEN r.kind = 'transcription' THEN r.audio_seconds END), 0.0) \
+         FROM day LEFT JOIN usage_records r ON r.timestamp >= day.start",
+    )
+    .fetch_one(&pool)
+    .await
+    .map_err(|e| format!("Failed to total today's usage: {}", e))?;
+
+    let read_error = |e: sqlx::Error| format!("Failed to read usage totals: {}", e);
+    Ok(DailyTotals {
+        day_start_ms: row.try_get(0).map_err(read_error)?,
+        tokens: row.try_get::<i64, _>(1).map_err(read_error)? as u64,
+        audio_seconds: row.try_get(2).map_err(read_error)?,
+    })
+}
+
+// Groups by `period_format` (an SQLite strftime pattern) in local time, from
+// the start of the current day or month (`start_of`) moved back by `offset`
+async fn aggregate(
+    app: &AppHandle,
+    period_format: &str,
+    start_of: &str,
+    offset: String,
+) -> Result<Vec<UsageAggregate>, String> {
+    let pool = db::sqlite_pool(app).await?;
+    let rows = sqlx::query(
+        "SELECT strftime(?, timestamp / 1000, 'unixepoch', 'localtime') AS period, \
+                kind, model, COUNT(*), SUM(prompt_tokens), SUM(completion_tokens), \
+                SUM(audio_seconds), CAST(AVG(latency_ms) AS INTEGER), SUM(estimated) \
+         FROM usage_records \
+         WHERE timestamp >= CAST(strftime('%s', 'now', 'localtime', ?, ?, 'utc') AS INTEGER) * 1000 \
+         GROUP BY period, kind, model \
+         ORDER BY period DESC, kind, model",
+    )
+    .bind(period_format)
+    .bind(start_of)
+    .bind(offset)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| format!("Failed to aggregate usage: {}", e))?;
+
+    let pricing = app.state::<UsageLedgerState>().pricing.load_or_default(app);
+
+    rows.iter()
+        .map(|row| {
+            let model: String = row.try_get(2)?;
+            let prompt_tokens = row.try_get::<i64, _>(4)? as u64;
+            let completion_tokens = row.try_get::<i64, _>(5)? as u64;
+            let audio_seconds: f64 = row.try_get(6)?;
+            let estimated_cost_usd = price_for(&pricing, &model)
+                .map(|price| estimate_cost(&price, prompt_tokens, completion_tokens, audio_seconds));
+
+            Ok(UsageAggregate {
+                period: row.try_get(0)?,
+                kind: row.try_get(1)?,
+                model,
+                requests: row.try_get::<i64, _>(3)? as u64,
+                prompt_tokens,
+                completion_tokens,
+                audio_seconds,
+                avg_latency_ms: row.try_get::<i64, _>(7)? as u64,
+                estimated_requests: row.try_get::<i64, _>(8)? as u64,
+                estimated_cost_usd,
+            })
+        })
+        .collect::<Result<Vec<_>, sqlx::Error>>()
+        .map_err(|e| format!("Failed to read usage records: {}", e))
+}
+
+// Per day, model and kind, today included
+#[tauri::command]
+pub async fn get_daily_usage(app: AppHandle, days: Option<u32>) -> Result<Vec<UsageAggregate>, String> {
+    let days = days.unwrap_or(DEFAULT_DAYS);
+    if !(1..=MAX_DAYS).contains(&days) {
+        return Err(format!("Invalid days: must be between 1 and {}", MAX_DAYS));
+    }
+    aggregate(&app, "%Y-%m-%d", "start of day", format!("-{} days", days - 1)).await
+}
+
+// Per month, model and kind, the current month included
+#[tauri::command]
+pub async fn get_monthly_usage(
+    app: AppHandle,
+    months: Option<u32>,
+) -> Result<Vec<UsageAggregate>, String> {
+    let months = months.unwrap_or(DEFAULT_MONTHS);
+    if !(1..=MAX_MONTHS).contains(&months) {
+        return Err(format!("Invalid months: must be between 1 and {}", MAX_MONTHS));
+    }
+    aggregate(&app, "%Y-%m", "start of month", format!("-{} months", months - 1)).await
+}
+
+#[tauri::command]
+pub fn get_usage_pricing(app: AppHandle) -> Result<UsagePricingConfig, String> {
+    load_config(&app)
+}
+
+#[tauri::command]
+pub fn update_usage_pricing(app: AppHandle, config: UsagePricingConfig) -> Result<(), String> {
+    for (model, price) in &config.prices {
+        if model.trim().is_empty() {
+            return Err("Invalid model: must not be empty".to_string());
+        }
+        let values = [price.input_per_million, price.output_per_million, price.audio_per_minute];
+        if values.iter().any(|value| !value.is_finite() || *value < 0.0) {
+            return Err(format!("Invalid price for {}: must be a non-negative number", model));
+        }
+    }
+
+    app.state::<UsageLedgerState>().pricing.save(&app, config)
+}
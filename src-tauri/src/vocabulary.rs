@@ -0,0 +1,322 @@
+// Custom vocabulary and transcript replacement rules. Terms from the active
+// profile are sent as a hint to STT providers that accept one, and the rules
+// run over every transcription before it's handed back to the frontend.
+use crate::api::TranscriptionResult;
+use crate::config_store::{JsonConfig, JsonConfigStore};
+use regex::{NoExpand, Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager};
+use uuid::Uuid;
+
+// Whisper only looks at the last 224 prompt tokens, keep the hint short
+const MAX_PROMPT_CHARS: usize = 800;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplacementKind {
+    Literal,
+    Regex,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplacementRule {
+    pub kind: ReplacementKind,
+    pub pattern: String,
+    pub replacement: String,
+    #[serde(default)]
+    pub case_sensitive: bool,
+    // Literal rules only: don't match inside longer words
+    #[serde(default = "default_true")]
+    pub whole_word: bool,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VocabularyProfile {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub terms: Vec<String>,
+    #[serde(default)]
+    pub rules: Vec<ReplacementRule>,
+    // Rewrite case-insensitive matches of each term to its canonical casing
+    #[serde(default = "default_true")]
+    pub fix_term_casing: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct VocabularyStore {
+    pub active_profile_id: Option<String>,
+    pub profiles: Vec<VocabularyProfile>,
+}
+
+impl JsonConfig for VocabularyStore {
+    const FILE_NAME: &'static str = "vocabulary.json";
+    const LABEL: &'static str = "vocabulary";
+}
+
+/// A profile with its rules compiled, so transcripts (every streamed partial
+/// included) don't rebuild the regexes.
+pub struct CompiledProfile {
+    pub profile: VocabularyProfile,
+    // Enabled, valid rules in order, with their replacement
+    rules: Vec<(Regex, ReplacementKind, String)>,
+    // Case-insensitive term matchers and the canonical casing
+    casing: Vec<(Regex, String)>,
+}
+
+impl CompiledProfile {
+    pub fn new(profile: VocabularyProfile) -> Self {
+        let mut rules = Vec::new();
+        for rule in profile.rules.iter().filter(|rule| rule.enabled) {
+            match compile_rule(rule) {
+                Ok(regex) => rules.push((regex, rule.kind, rule.replacement.clone())),
+                Err(e) => tracing::warn!("Skipping invalid replacement rule: {}", e),
+            }
+        }
+
+        let casing = if profile.fix_term_casing {
+            terms(&profile)
+                .into_iter()
+                .filter_map(|term| Some((literal_regex(&term, false, true).ok()?, term)))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        Self {
+            profile,
+            rules,
+            casing,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct VocabularyState {
+    store: JsonConfigStore<VocabularyStore>,
+    // None until first use, then the active profile (if any), rebuilt
+    // whenever the store is saved
+    active: Mutex<Option<Option<Arc<CompiledProfile>>>>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn load_store(app: &AppHandle) -> Result<VocabularyStore, String> {
+    app.state::<VocabularyState>().store.load(app)
+}
+
+fn compile_active(store: &VocabularyStore) -> Option<Arc<CompiledProfile>> {
+    let active_id = store.active_profile_id.as_ref()?;
+    store
+        .profiles
+        .iter()
+        .find(|profile| &profile.id == active_id)
+        .map(|profile| Arc::new(CompiledProfile::new(profile.clone())))
+}
+
+fn save_store(app: &AppHandle, store: VocabularyStore) -> Result<(), String> {
+    let active = compile_active(&store);
+    let state = app.state::<VocabularyState>();
+    state.store.save(app, store)?;
+
+    let mut cached = match state.active.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    *cached = Some(active);
+    Ok(())
+}
+
+/// The currently selected profile, if any. Errors are logged and treated as
+/// "no vocabulary" so a broken file never blocks transcription.
+pub fn active_profile(app: &AppHandle) -> Option<Arc<CompiledProfile>> {
+    let state = app.state::<VocabularyState>();
+    let mut cached = match state.active.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    if let Some(active) = cached.as_ref() {
+        return active.clone();
+    }
+
+    let store = match load_store(app) {
+        Ok(store) => store,
+        Err(e) => {
+            tracing::warn!("Failed to load vocabulary: {}", e);
+            return None;
+        }
+    };
+    cached.get_or_insert(compile_active(&store)).clone()
+}
+
+/// Comma-separated term list for the STT `prompt` field.
+pub fn build_prompt(profile: &VocabularyProfile) -> Option<String> {
+    let mut prompt = String::new();
+    for term in profile.terms.iter().map(|term| term.trim()) {
+        if term.is_empty() {
+            continue;
+        }
+        if prompt.len() + term.len() + 2 > MAX_PROMPT_CHARS {
+            break;
+        }
+        if !prompt.is_empty() {
+            prompt.push_str(", ");
+        }
+        prompt.push_str(term);
+    }
+
+    if prompt.is_empty() {
+        None
+    } else {
+        Some(prompt)
+    }
+}
+
+/// Non-empty, trimmed vocabulary terms (used as streaming keyterms).
+pub fn terms(profile: &VocabularyProfile) -> Vec<String> {
+    profile
+        .terms
+        .iter()
+        .map(|term| term.trim().to_string())
+        .filter(|term| !term.is_empty())
+        .collect()
+}
+
+pub fn apply_rules(profile: &CompiledProfile, text: &str) -> String {
+    let mut output = text.to_string();
+
+    for (regex, kind, replacement) in &profile.rules {
+        output = match kind {
+            ReplacementKind::Literal => regex
+                .replace_all(&output, NoExpand(replacement))
+                .into_owned(),
+            ReplacementKind::Regex => regex
+                .replace_all(&output, replacement.as_str())
+                .into_owned(),
+        };
+    }
+
+    for (regex, term) in &profile.casing {
+        output = regex.replace_all(&output, NoExpand(term)).into_owned();
+    }
+
+    output
+}
+
+pub fn apply_to_result(profile: &CompiledProfile, result: &mut TranscriptionResult) {
+    result.text = apply_rules(profile, &result.text);
+    for segment in result.segments.iter_mut() {
+        segment.text = apply_rules(profile, &segment.text);
+    }
+}
+
+fn compile_rule(rule: &ReplacementRule) -> Result<Regex, String> {
+    if rule.pattern.is_empty() {
+        return Err("pattern is empty".to_string());
+    }
+
+    match rule.kind {
+        ReplacementKind::Literal => literal_regex(&rule.pattern, rule.case_sensitive, rule.whole_word),
+        ReplacementKind::Regex => RegexBuilder::new(&rule.pattern)
+            .case_insensitive(!rule.case_sensitive)
+            .build()
+            .map_err(|e| format!("invalid regex '{}': {}", rule.pattern, e)),
+    }
+}
+
+fn literal_regex(pattern: &str, case_sensitive: bool, whole_word: bool) -> Result<Regex, String> {
+    let mut source = regex::escape(pattern);
+
+    // \b only makes sense next to word characters ("C++" can't end on one)
+    if whole_word {
+        if pattern.chars().next().is_some_and(is_word_char) {
+            source = format!(r"\b{}", source);
+        }
+        if pattern.chars().last().is_some_and(is_word_char) {
+            source = format!(r"{}\b", source);
+        }
+    }
+
+    RegexBuilder::new(&source)
+        .case_insensitive(!case_sensitive)
+        .build()
+        .map_err(|e| format!("invalid pattern '{}': {}", pattern, e))
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn validate_profile(profile: &VocabularyProfile) -> Result<(), String> {
+    if profile.name.trim().is_empty() {
+        return Err("Vocabulary profile name cannot be empty".to_string());
+    }
+
+    for (idx, rule) in profile.rules.iter().enumerate() {
+        compile_rule(rule).map_err(|e| format!("Rule {}: {}", idx + 1, e))?;
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_vocabulary_profiles(app: AppHandle) -> Result<VocabularyStore, String> {
+    load_store(&app)
+}
+
+#[tauri::command]
+pub fn save_vocabulary_profile(
+    app: AppHandle,
+    profile: VocabularyProfile,
+) -> Result<VocabularyProfile, String> {
+    validate_profile(&profile)?;
+
+    let mut profile = profile;
+    if profile.id.trim().is_empty() {
+        profile.id = Uuid::new_v4().to_string();
+    }
+
+    let mut store = load_store(&app)?;
+    match store.profiles.iter_mut().find(|p| p.id == profile.id) {
+        Some(existing) => *existing = profile.clone(),
+        None => store.profiles.push(profile.clone()),
+    }
+    save_store(&app, store)?;
+
+    Ok(profile)
+}
+
+#[tauri::command]
+pub fn delete_vocabulary_profile(app: AppHandle, id: String) -> Result<(), String> {
+    let mut store = load_store(&app)?;
+    store.profiles.retain(|profile| profile.id != id);
+    if store.active_profile_id.as_deref() == Some(id.as_str()) {
+        store.active_profile_id = None;
+    }
+    save_store(&app, store)
+}
+
+#[tauri::command]
+pub fn set_active_vocabulary_profile(app: AppHandle, id: Option<String>) -> Result<(), String> {
+    let mut store = load_store(&app)?;
+    if let Some(id) = id.as_ref() {
+        if !store.profiles.iter().any(|profile| &profile.id == id) {
+            return Err(format!("Vocabulary profile not found: {}", id));
+        }
+    }
+    store.active_profile_id = id;
+    save_store(&app, store)
+}
+
+// Lets the settings UI try out rules before saving them
+#[tauri::command]
+pub fn preview_vocabulary_rules(profile: VocabularyProfile, text: String) -> Result<String, String> {
+    validate_profile(&profile)?;
+    Ok(apply_rules(&CompiledProfile::new(profile), &text))
+}
@@ -3,7 +3,11 @@ import { useWindowResize, useGlobalShortcuts } from ".";
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 import { useApp } from "@/contexts";
-import { fetchSTT, fetchAIResponse } from "@/lib/functions";
+import {
+  fetchSTT,
+  fetchAIResponse,
+  listenQueuedTranscriptions,
+} from "@/lib/functions";
 import {
   DEFAULT_QUICK_ACTIONS,
   DEFAULT_SYSTEM_PROMPT,
@@ -221,6 +225,30 @@ export function useSystemAudio() {
   // Handle single speech detection event (both VAD and continuous modes)
   useEffect(() => {
     let speechUnlisten: (() => void) | undefined;
+    let queuedUnlisten: (() => void) | undefined;
+
+    const handleTranscription = async (transcription: string) => {
+      if (transcription.trim()) {
+        setLastTranscription(transcription);
+        setError("");
+
+        const effectiveSystemPrompt = useSystemPrompt
+          ? systemPrompt || DEFAULT_SYSTEM_PROMPT
+          : contextContent || DEFAULT_SYSTEM_PROMPT;
+
+        const previousMessages = conversation.messages.map((msg) => {
+          return { role: msg.role, content: msg.content };
+        });
+
+        await processWithAI(
+          transcription,
+          effectiveSystemPrompt,
+          previousMessages
+        );
+      } else {
+        setError("Received empty transcription");
+      }
+    };
 
     const setupEventListener = async () => {
       try {
@@ -274,26 +302,7 @@ export function useSystemAudio() {
                 timeoutPromise,
               ]);
 
-              if (transcription.trim()) {
-                setLastTranscription(transcription);
-                setError("");
-
-                const effectiveSystemPrompt = useSystemPrompt
-                  ? systemPrompt || DEFAULT_SYSTEM_PROMPT
-                  : contextContent || DEFAULT_SYSTEM_PROMPT;
-
-                const previousMessages = conversation.messages.map((msg) => {
-                  return { role: msg.role, content: msg.content };
-                });
-
-                await processWithAI(
-                  transcription,
-                  effectiveSystemPrompt,
-                  previousMessages
-                );
-              } else {
-                setError("Received empty transcription");
-              }
+              await handleTranscription(transcription);
             } catch (sttError: any) {
               console.error("STT Error:", sttError);
               setError(sttError.message || "Failed to transcribe audio");
@@ -305,6 +314,28 @@ export function useSystemAudio() {
             setIsProcessing(false);
           }
         });
+
+        // Segments already transcribed by the Rust queue, when it's enabled
+        queuedUnlisten = await listenQueuedTranscriptions(
+          "speaker",
+          async (transcription) => {
+            if (!capturing) return;
+            try {
+              setIsProcessing(true);
+              await handleTranscription(transcription);
+            } catch (err) {
+              setError("Failed to process speech");
+            } finally {
+              setIsProcessing(false);
+            }
+          },
+          (transcriptionError) => {
+            if (!capturing) return;
+            console.error("STT Error:", transcriptionError);
+            setError(transcriptionError);
+            setIsPopoverOpen(true);
+          }
+        );
       } catch (err) {
         setError("Failed to setup speech listener");
       }
@@ -314,6 +345,7 @@ export function useSystemAudio() {
 
     return () => {
       if (speechUnlisten) speechUnlisten();
+      if (queuedUnlisten) queuedUnlisten();
     };
   }, [
     capturing,
@@ -10,7 +10,7 @@ import { fetch as tauriFetch } from "@tauri-apps/plugin-http";
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 import curl2Json from "@bany/curl-to-json";
-import { shouldUsePluelyAPI } from "./pluely.api";
+import { shouldUseDirectProvider, shouldUsePluelyAPI } from "./pluely.api";
 import { CHUNK_POLL_INTERVAL_MS } from "../chat-constants";
 import { getResponseSettings, RESPONSE_LENGTHS, LANGUAGES } from "@/lib";
 
@@ -78,18 +78,33 @@ async function* fetchPluelyAIResponse(params: {
       imageBase64 = imagesBase64.length === 1 ? imagesBase64[0] : imagesBase64;
     }
 
-    // Set up streaming event listener
+    // Set up streaming event listener. Events are global, so only pick up
+    // the ones for this request (other streams may be running concurrently).
     let streamComplete = false;
     const streamChunks: string[] = [];
+    const requestId = crypto.randomUUID();
 
-    const unlisten = await listen("chat_stream_chunk", (event) => {
-      const chunk = event.payload as string;
-      streamChunks.push(chunk);
-    });
+    const unlisten = await listen<{ request_id: string; content: string }>(
+      "chat_stream_chunk",
+      (event) => {
+        if (event.payload.request_id !== requestId) return;
+        streamChunks.push(event.payload.content);
+      }
+    );
+
+    const unlistenComplete = await listen<{ request_id: string }>(
+      "chat_stream_complete",
+      (event) => {
+        if (event.payload.request_id !== requestId) return;
+        streamComplete = true;
+      }
+    );
 
-    const unlistenComplete = await listen("chat_stream_complete", () => {
-      streamComplete = true;
-    });
+    // Lets an abort stop the Rust-side stream instead of just ignoring it
+    const cancelStream = () => {
+      invoke("cancel_chat_stream", { requestId }).catch(() => {});
+    };
+    signal?.addEventListener("abort", cancelStream, { once: true });
 
     try {
       // Check if aborted before starting invoke
@@ -105,6 +120,7 @@ async function* fetchPluelyAIResponse(params: {
         systemPrompt,
         imageBase64,
         history: historyString,
+        requestId,
       });
 
       // Yield chunks as they come in
@@ -148,6 +164,7 @@ async function* fetchPluelyAIResponse(params: {
         yield streamChunks[i];
       }
     } finally {
+      signal?.removeEventListener("abort", cancelStream);
       unlisten();
       unlistenComplete();
     }
@@ -187,8 +204,9 @@ export async function* fetchAIResponse(params: {
 
     const enhancedSystemPrompt = buildEnhancedSystemPrompt(systemPrompt);
 
-    // Check if we should use Pluely API instead
-    const usePluelyAPI = await shouldUsePluelyAPI();
+    // Check if we should use Pluely API (or a direct endpoint) instead
+    const usePluelyAPI =
+      (await shouldUseDirectProvider()) || (await shouldUsePluelyAPI());
     if (usePluelyAPI) {
       yield* fetchPluelyAIResponse({
         systemPrompt: enhancedSystemPrompt,
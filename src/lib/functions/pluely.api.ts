@@ -18,3 +18,17 @@ export async function shouldUsePluelyAPI(): Promise<boolean> {
     return false;
   }
 }
+
+// Direct mode sends chat straight from Rust to a locally configured endpoint,
+// no license required
+export async function shouldUseDirectProvider(): Promise<boolean> {
+  try {
+    const config = await invoke<{ enabled: boolean }>(
+      "get_direct_provider_config"
+    );
+    return config.enabled;
+  } catch (error) {
+    console.warn("Failed to read direct provider config:", error);
+    return false;
+  }
+}
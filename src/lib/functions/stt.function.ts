@@ -5,6 +5,7 @@ import {
 } from "./common.function";
 import { fetch as tauriFetch } from "@tauri-apps/plugin-http";
 import { invoke } from "@tauri-apps/api/core";
+import { listen, UnlistenFn } from "@tauri-apps/api/event";
 
 import { TYPE_PROVIDER } from "@/types";
 import curl2Json from "@bany/curl-to-json";
@@ -25,8 +26,9 @@ async function fetchPluelySTT(audio: File | Blob): Promise<string> {
       audioBase64,
     });
 
-    if (response.success && response.transcription) {
-      return response.transcription;
+    if (response.success) {
+      // Empty when the hallucination filter dropped the whole segment
+      return response.transcription ?? "";
     } else {
       return response.error || "Transcription failed";
     }
@@ -247,3 +249,33 @@ export async function fetchSTT(params: STTParams): Promise<string> {
     throw new Error(msg);
   }
 }
+
+// Payload of `transcription-result`, emitted in capture order when the
+// Rust transcription queue is enabled (it then replaces `speech-detected`
+// and `mic-speech-detected`)
+export interface TranscriptionQueueEvent {
+  seq: number;
+  source: "speaker" | "mic";
+  result: { text: string } | null;
+  error: string | null;
+}
+
+/**
+ * Listens for segments transcribed by the Rust queue for one capture source.
+ */
+export async function listenQueuedTranscriptions(
+  source: TranscriptionQueueEvent["source"],
+  onTranscription: (text: string) => void,
+  onError: (error: string) => void
+): Promise<UnlistenFn> {
+  return listen<TranscriptionQueueEvent>("transcription-result", (event) => {
+    const { payload } = event;
+    if (payload.source !== source) return;
+
+    if (payload.error) {
+      onError(payload.error);
+    } else {
+      onTranscription(payload.result?.text.trim() ?? "");
+    }
+  });
+}
@@ -1,4 +1,4 @@
-import { fetchSTT } from "@/lib";
+import { fetchSTT, listenQueuedTranscriptions } from "@/lib";
 import { UseCompletionReturn } from "@/types";
 import { LoaderCircleIcon, MicIcon, MicOffIcon } from "lucide-react";
 import { useState, useEffect, useRef, useCallback } from "react";
@@ -54,6 +54,17 @@ const AutoSpeechVADInternal = ({
     }
   }, [waitingForResponse, response, stopMic]);
 
+  const handleTranscription = useCallback(
+    (transcription: string) => {
+      if (transcription) {
+        // Mark that we're waiting for the first response character to stop mic
+        setWaitingForResponse(true);
+        submit(transcription);
+      }
+    },
+    [submit]
+  );
+
   // Handle speech detected from Rust backend
   const handleSpeechDetected = useCallback(
     async (base64Audio: string) => {
@@ -97,11 +108,7 @@ const AutoSpeechVADInternal = ({
           audio: audioBlob,
         });
 
-        if (transcription) {
-          // Mark that we're waiting for the first response character to stop mic
-          setWaitingForResponse(true);
-          submit(transcription);
-        }
+        handleTranscription(transcription);
       } catch (error) {
         console.error("Failed to transcribe audio:", error);
         setState((prev: any) => ({
@@ -113,7 +120,7 @@ const AutoSpeechVADInternal = ({
         setIsTranscribing(false);
       }
     },
-    [selectedSttProvider, allSttProviders, submit, setState]
+    [selectedSttProvider, allSttProviders, handleTranscription, setState]
   );
 
   // Start mic capture helper
@@ -134,7 +141,18 @@ const AutoSpeechVADInternal = ({
           handleSpeechDetected(event.payload);
         }
       );
-      unlistenRef.current = unlisten;
+      // Segments already transcribed by the Rust queue, when it's enabled
+      const unlistenQueued = await listenQueuedTranscriptions(
+        "mic",
+        handleTranscription,
+        (error) => {
+          setState((prev: any) => ({ ...prev, error }));
+        }
+      );
+      unlistenRef.current = () => {
+        unlisten();
+        unlistenQueued();
+      };
     } catch (error) {
       console.error("Failed to start mic capture:", error);
       setState((prev: any) => ({
@@ -142,7 +160,13 @@ const AutoSpeechVADInternal = ({
         error: `Mic capture failed: ${error}`,
       }));
     }
-  }, [microphoneDeviceId, setEnableVAD, setState, handleSpeechDetected]);
+  }, [
+    microphoneDeviceId,
+    setEnableVAD,
+    setState,
+    handleSpeechDetected,
+    handleTranscription,
+  ]);
 
   // Handle mic button click
   const handleMicClick = async () => {
@@ -1,7 +1,11 @@
 import { useState, useRef, useEffect } from "react";
 import { Button } from "@/components";
 import { AudioVisualizer } from "@/pages/app/components/speech/audio-visualizer";
-import { shouldUsePluelyAPI, fetchSTT } from "@/lib";
+import {
+  shouldUsePluelyAPI,
+  fetchSTT,
+  listenQueuedTranscriptions,
+} from "@/lib";
 import { useApp } from "@/contexts";
 import { StopCircle, Send } from "lucide-react";
 import { invoke } from "@tauri-apps/api/core";
@@ -24,6 +28,8 @@ export const AudioRecorder = ({
   const [isRecording, setIsRecording] = useState(false);
 
   const audioChunksRef = useRef<string[]>([]);
+  // Filled instead of audioChunksRef when the Rust queue transcribes segments
+  const transcribedChunksRef = useRef<string[]>([]);
   const startTimeRef = useRef<number>(0);
   const durationIntervalRef = useRef<NodeJS.Timeout | null>(null);
   const maxDurationTimeoutRef = useRef<NodeJS.Timeout | null>(null);
@@ -59,6 +65,7 @@ export const AudioRecorder = ({
       setIsRecording(true);
 
       audioChunksRef.current = [];
+      transcribedChunksRef.current = [];
       startTimeRef.current = Date.now();
 
       // Listen for speech segments from Rust backend
@@ -68,7 +75,19 @@ export const AudioRecorder = ({
           audioChunksRef.current.push(event.payload);
         }
       );
-      unlistenRef.current = unlisten;
+      const unlistenQueued = await listenQueuedTranscriptions(
+        "mic",
+        (text) => {
+          transcribedChunksRef.current.push(text);
+        },
+        (error) => {
+          console.error("Queued transcription failed:", error);
+        }
+      );
+      unlistenRef.current = () => {
+        unlisten();
+        unlistenQueued();
+      };
 
       durationIntervalRef.current = setInterval(() => {
         setDuration(Date.now() - startTimeRef.current);
@@ -96,9 +115,16 @@ export const AudioRecorder = ({
 
     // Get the last speech segment (most recent complete utterance)
     const lastChunk = audioChunksRef.current[audioChunksRef.current.length - 1];
+    const lastText =
+      transcribedChunksRef.current[transcribedChunksRef.current.length - 1];
 
     cleanup();
 
+    if (!lastChunk && lastText) {
+      onTranscriptionComplete(lastText);
+      return;
+    }
+
     if (!lastChunk) {
       // No speech detected — cancel
       onCancel();